@@ -0,0 +1,74 @@
+//! Benchmarks for building an index, with and without the `rayon` feature:
+//!
+//! ```sh
+//! cargo bench --bench build
+//! cargo bench --bench build --features rayon
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use supercluster_rs::SuperclusterBuilder;
+
+const NUM_POINTS: usize = 100_000;
+
+/// A deterministic pseudo-random number generator, so that runs are comparable.
+struct XorShift(u64);
+
+impl XorShift {
+    /// A pseudo-random number in `[0, 1)`.
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Points spread evenly over the world.
+fn uniform_points() -> Vec<(f64, f64)> {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    (0..NUM_POINTS)
+        .map(|_| (rng.next() * 360.0 - 180.0, rng.next() * 170.0 - 85.0))
+        .collect()
+}
+
+/// Points concentrated in a few small areas, with many exact duplicates, so that most points are
+/// absorbed into a handful of clusters at every zoom.
+fn dense_points() -> Vec<(f64, f64)> {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let centers = [
+        (-73.98, 40.75),
+        (2.35, 48.86),
+        (139.69, 35.69),
+        (-46.63, -23.55),
+    ];
+    (0..NUM_POINTS)
+        .map(|i| {
+            let (x, y) = centers[i % centers.len()];
+            if i % 2 == 0 {
+                (x, y)
+            } else {
+                (x + rng.next() * 0.01, y + rng.next() * 0.01)
+            }
+        })
+        .collect()
+}
+
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+    for (name, points) in [("uniform", uniform_points()), ("dense", dense_points())] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &points, |b, points| {
+            b.iter(|| {
+                let mut builder = SuperclusterBuilder::new(points.len());
+                for (x, y) in points {
+                    builder.add(*x, *y);
+                }
+                builder.finish()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_build);
+criterion_main!(benches);
@@ -37,7 +37,7 @@ fn clusters_to_record_batch(clusters: Vec<ClusterInfo>) -> RecordBatch {
     let mut point_count_arr = UInt32Builder::with_capacity(clusters.len());
 
     for cluster in clusters {
-        id_arr.append_value(usize::from(cluster.id()).try_into().unwrap());
+        id_arr.append_value(u64::from(cluster.id()));
         x_arr.append_value(cluster.x());
         y_arr.append_value(cluster.y());
         is_cluster_arr.append_value(cluster.is_cluster());
@@ -0,0 +1,58 @@
+/// Aggregate properties of input points into a value stored on every cluster.
+///
+/// This is akin to the `map` and `reduce` options of the original JS implementation. `map` is
+/// called once for each input point, and `reduce` is called each time a point or cluster is merged
+/// into a cluster.
+///
+/// ```
+/// use supercluster_rs::{ClusterAggregator, SuperclusterBuilder};
+///
+/// /// Keep track of the sum and maximum of a per-point value.
+/// struct SumMax(Vec<f64>);
+///
+/// impl ClusterAggregator for SumMax {
+///     type Accumulator = (f64, f64);
+///
+///     fn map(&self, point_index: usize) -> Self::Accumulator {
+///         (self.0[point_index], self.0[point_index])
+///     }
+///
+///     fn reduce(&self, accumulated: &mut Self::Accumulator, other: &Self::Accumulator) {
+///         accumulated.0 += other.0;
+///         accumulated.1 = accumulated.1.max(other.1);
+///     }
+/// }
+///
+/// let mut builder = SuperclusterBuilder::new(2);
+/// builder.add(10.0, 10.0);
+/// builder.add(10.0001, 10.0001);
+/// let supercluster = builder.finish_with_aggregator(&SumMax(vec![3.0, 4.0]));
+///
+/// let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 0);
+/// assert_eq!(clusters[0].properties(), &(7.0, 4.0));
+/// ```
+pub trait ClusterAggregator {
+    /// The value accumulated on each cluster.
+    type Accumulator: Clone;
+
+    /// Create the initial value for the input point at `point_index`.
+    ///
+    /// `point_index` counts the points in the order they were added: it is the position returned
+    /// by [`SuperclusterBuilder::add`][crate::SuperclusterBuilder::add] and its variants,
+    /// including [`add_with_id`][crate::SuperclusterBuilder::add_with_id], followed by the points
+    /// inserted with [`Supercluster::insert_with_aggregator`][crate::Supercluster::insert_with_aggregator]
+    /// and its variants. It does not depend on point ids.
+    fn map(&self, point_index: usize) -> Self::Accumulator;
+
+    /// Merge the value of `other` into `accumulated`.
+    fn reduce(&self, accumulated: &mut Self::Accumulator, other: &Self::Accumulator);
+}
+
+/// The default aggregator, which does not store any properties on clusters.
+impl ClusterAggregator for () {
+    type Accumulator = ();
+
+    fn map(&self, _point_index: usize) -> Self::Accumulator {}
+
+    fn reduce(&self, _accumulated: &mut Self::Accumulator, _other: &Self::Accumulator) {}
+}
@@ -1,20 +1,115 @@
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
 use geo_index::kdtree::KDTreeIndex;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
+use crate::aggregator::ClusterAggregator;
 use crate::cluster::{ClusterData, ClusterId};
+use crate::error::SuperclusterError;
 use crate::options::SuperclusterOptions;
+use crate::projection::{MercatorUnits, Planar, Projection, WebMercator};
+use crate::r#type::ClusterFloat;
 use crate::tree::TreeWithData;
+use crate::util::extend_bounds;
 use crate::Supercluster;
 
+/// The largest number of neighbor searches run in parallel before merging their results.
+#[cfg(feature = "rayon")]
+const MAX_NEIGHBOR_BATCH_SIZE: usize = 1 << 16;
+
+/// The largest number of neighbor ids held at once by a batch of neighbor searches.
+#[cfg(feature = "rayon")]
+const MAX_NEIGHBOR_BATCH_IDS: usize = 1 << 22;
+
+/// How [SuperclusterBuilder] handles points with non-finite coordinates, or coordinates outside of
+/// the [bounds][Projection::bounds] of its projection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoordinatePolicy {
+    /// Return [`SuperclusterError::InvalidCoordinate`] from
+    /// [`try_finish`][SuperclusterBuilder::try_finish].
+    Reject,
+
+    /// Leave the points out of the index. Their ids are still reserved, and are listed by
+    /// [`Supercluster::skipped_points`].
+    Skip,
+
+    /// Move out of range points within the bounds, and list their ids in
+    /// [`Supercluster::clamped_points`]. If x is [periodic][Projection::periodic_x], as longitude
+    /// is, it is wrapped around the antimeridian; other coordinates are moved to the nearest bound.
+    /// Non-finite coordinates are still rejected.
+    #[default]
+    Clamp,
+}
+
+/// What a [CoordinatePolicy] did with a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CoordinateAction {
+    Keep,
+    Skip,
+    Clamp,
+}
+
+impl CoordinatePolicy {
+    /// Apply this policy to the point at position `index`, moving it within the bounds of the
+    /// projection if it is clamped.
+    pub(crate) fn apply(
+        self,
+        index: usize,
+        point: &mut (f64, f64),
+        projection: &dyn Projection,
+    ) -> Result<CoordinateAction, SuperclusterError> {
+        let [min_x, min_y, max_x, max_y] = projection.bounds();
+        let (x, y) = point;
+        let finite = x.is_finite() && y.is_finite();
+        if finite && (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y) {
+            return Ok(CoordinateAction::Keep);
+        }
+
+        match (self, finite) {
+            (CoordinatePolicy::Skip, _) => Ok(CoordinateAction::Skip),
+            (CoordinatePolicy::Clamp, true) => {
+                if !(min_x..=max_x).contains(x) {
+                    *x = if projection.periodic_x() {
+                        min_x + (*x - min_x).rem_euclid(max_x - min_x)
+                    } else {
+                        x.clamp(min_x, max_x)
+                    };
+                }
+                *y = y.clamp(min_y, max_y);
+                Ok(CoordinateAction::Clamp)
+            }
+            _ => Err(SuperclusterError::InvalidCoordinate {
+                index,
+                x: *x,
+                y: *y,
+            }),
+        }
+    }
+}
+
 /// A data class used to construct a [Supercluster] instance.
-pub struct SuperclusterBuilder {
+///
+/// The type parameter `N` is the floating point type used to store projected coordinates in the
+/// index. It defaults to `f64`; use `f32` to roughly halve the memory used by large indexes.
+pub struct SuperclusterBuilder<N: ClusterFloat = f64> {
     options: SuperclusterOptions,
     // TODO: in the future, this should be a chunked array of geoarrow points
     points: Vec<(f64, f64)>,
+    // The weight of each point, which defaults to 1
+    weights: Vec<f64>,
+    // The category of each point, which defaults to 0
+    categories: Vec<u32>,
+    // The id of each point, which defaults to its position
+    ids: Vec<u64>,
     pos: usize,
-    // If points are already in spherical mercator
-    // preprojected: bool,
+    // The projection from the user's coordinate system to the unit square
+    projection: Arc<dyn Projection>,
+    // How invalid coordinates are handled
+    coordinate_policy: CoordinatePolicy,
+    phantom: PhantomData<N>,
 }
 
 impl SuperclusterBuilder {
@@ -23,49 +118,354 @@ impl SuperclusterBuilder {
         Self::new_with_options(num_items, Default::default())
     }
 
-    /// Construct a new [SuperclusterBuilder] with the given number of points and default options.
+    /// Construct a new [SuperclusterBuilder] with the given number of points and options.
     pub fn new_with_options(num_items: usize, options: SuperclusterOptions) -> Self {
+        Self::new_with_coord_type(num_items, options)
+    }
+}
+
+impl<N: ClusterFloat> SuperclusterBuilder<N> {
+    /// Construct a new [SuperclusterBuilder] with the given number of points and options, storing
+    /// projected coordinates as `N`.
+    ///
+    /// ```
+    /// use supercluster_rs::SuperclusterBuilder;
+    ///
+    /// let mut builder = SuperclusterBuilder::<f32>::new_with_coord_type(1, Default::default());
+    /// builder.add(-79.04, 43.08);
+    /// let supercluster = builder.finish();
+    /// ```
+    pub fn new_with_coord_type(num_items: usize, options: SuperclusterOptions) -> Self {
         let points = Vec::with_capacity(num_items);
+        let weights = Vec::with_capacity(num_items);
+        let categories = Vec::with_capacity(num_items);
+        let ids = Vec::with_capacity(num_items);
 
         Self {
             options,
             points,
+            weights,
+            categories,
+            ids,
             pos: 0,
+            projection: Arc::new(WebMercator::default()),
+            coordinate_policy: CoordinatePolicy::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Set the units of the points added to this builder.
+    ///
+    /// By default, points are expected to be longitude and latitude in degrees. Use
+    /// [`MercatorUnits::Meters`] or [`MercatorUnits::Normalized`] if points are already projected
+    /// to Web Mercator. Query bounds and returned coordinates will then be in the same units.
+    pub fn with_units(self, units: MercatorUnits) -> Self {
+        self.with_projection(WebMercator::new(units))
+    }
+
+    /// Cluster non-geographic points, such as scatterplots or image annotations, within the given
+    /// extent.
+    ///
+    /// Points are expected to lie within the extent. Query bounds and returned coordinates will be
+    /// in the same units as the input points, and are not wrapped around the antimeridian. See
+    /// [`Planar`].
+    ///
+    /// If the extent is empty or not finite, [`try_finish`][Self::try_finish] returns
+    /// [`SuperclusterError::InvalidBounds`].
+    pub fn with_planar_extent(self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        self.with_projection(Planar::new(min_x, min_y, max_x, max_y))
+    }
+
+    /// Set the projection of the points added to this builder.
+    ///
+    /// By default, points are projected with [`WebMercator`]. Query bounds and returned
+    /// coordinates will be in the same coordinate system as the input points.
+    pub fn with_projection(self, projection: impl Projection + 'static) -> Self {
+        Self {
+            projection: Arc::new(projection),
+            ..self
+        }
+    }
+
+    /// Set how points with non-finite coordinates, or coordinates outside of the bounds of the
+    /// projection, are handled. Defaults to [`CoordinatePolicy::Clamp`].
+    pub fn with_coordinate_policy(self, coordinate_policy: CoordinatePolicy) -> Self {
+        Self {
+            coordinate_policy,
+            ..self
         }
     }
 
     // Add a point to the index
     pub fn add(&mut self, x: f64, y: f64) -> usize {
+        self.add_weighted(x, y, 1.0)
+    }
+
+    /// Add a point with the given weight to the index.
+    ///
+    /// A cluster is only formed once the total weight of its points reaches
+    /// [`min_points`][SuperclusterOptions::min_points], and its center is the weighted average of
+    /// its points. Points added with [`add`][Self::add] have a weight of 1.
+    ///
+    /// If `weight` is not positive and finite, [`try_finish`][Self::try_finish] returns
+    /// [`SuperclusterError::InvalidWeight`].
+    pub fn add_weighted(&mut self, x: f64, y: f64, weight: f64) -> usize {
+        self.add_weighted_with_category(x, y, weight, 0)
+    }
+
+    /// Add a point in the given category to the index.
+    ///
+    /// Points are only clustered with points of the same category, so that a single index can
+    /// hold several layers of points that are clustered separately. Points added with
+    /// [`add`][Self::add] are in category `0`.
+    pub fn add_with_category(&mut self, x: f64, y: f64, category: u32) -> usize {
+        self.add_weighted_with_category(x, y, 1.0, category)
+    }
+
+    /// Add a point with the given weight and category to the index. See
+    /// [`add_weighted`][Self::add_weighted] and [`add_with_category`][Self::add_with_category].
+    pub fn add_weighted_with_category(
+        &mut self,
+        x: f64,
+        y: f64,
+        weight: f64,
+        category: u32,
+    ) -> usize {
+        let id = self.pos as u64;
+        self.add_weighted_with_category_and_id(x, y, weight, category, id)
+    }
+
+    /// Add a point with the given id to the index, returning its position.
+    ///
+    /// Leaves returned by queries have the id of their point, rather than its position, and
+    /// clusters have an id derived from the smallest id among their points. A cluster keeps its
+    /// id across rebuilds as long as it has the same points, whatever order they were added in
+    /// and whatever other points are added or removed. Points added with [`add`][Self::add] have
+    /// their position as id.
+    ///
+    /// Ids must be unique and at most [`ClusterId::MAX_POINT_ID`], otherwise
+    /// [`try_finish`][Self::try_finish] returns an error.
+    pub fn add_with_id(&mut self, x: f64, y: f64, id: u64) -> usize {
+        self.add_weighted_with_category_and_id(x, y, 1.0, 0, id)
+    }
+
+    /// Add a point with the given weight, category and id to the index. See
+    /// [`add_weighted_with_category`][Self::add_weighted_with_category] and
+    /// [`add_with_id`][Self::add_with_id].
+    pub fn add_weighted_with_category_and_id(
+        &mut self,
+        x: f64,
+        y: f64,
+        weight: f64,
+        category: u32,
+        id: u64,
+    ) -> usize {
         let idx = self.pos;
         self.points.push((x, y));
+        self.weights.push(weight);
+        self.categories.push(category);
+        self.ids.push(id);
         self.pos += 1;
         idx
     }
 
     /// Convert a [SuperclusterBuilder] to a [Supercluster] by running hierarchical clustering.
-    pub fn finish(self) -> Supercluster {
-        assert_eq!(
-            self.pos,
-            self.points.len(),
-            "Expected {} added points, got {}.",
-            self.points.len(),
-            self.pos
-        );
+    ///
+    /// ## Panics
+    ///
+    /// If the options or points are invalid. Use [`try_finish`][Self::try_finish] to handle these
+    /// errors instead.
+    pub fn finish(self) -> Supercluster<N> {
+        self.finish_with_aggregator(&())
+    }
 
-        let min_zoom = self.options.min_zoom;
-        let max_zoom = self.options.max_zoom;
-        let node_size = self.options.node_size;
+    /// Convert a [SuperclusterBuilder] to a [Supercluster] by running hierarchical clustering,
+    /// accumulating properties onto each cluster with the provided [ClusterAggregator].
+    ///
+    /// ## Panics
+    ///
+    /// If the options or points are invalid. Use
+    /// [`try_finish_with_aggregator`][Self::try_finish_with_aggregator] to handle these errors
+    /// instead.
+    pub fn finish_with_aggregator<A: ClusterAggregator>(
+        self,
+        aggregator: &A,
+    ) -> Supercluster<N, A::Accumulator> {
+        self.try_finish_with_aggregator(aggregator).unwrap()
+    }
+
+    /// Convert a [SuperclusterBuilder] to a [Supercluster] by running hierarchical clustering,
+    /// returning an error if the options are invalid (see [`SuperclusterOptions::validate`]), the
+    /// bounds of the projection are empty or not finite, a point has an invalid coordinate that
+    /// the [CoordinatePolicy] doesn't handle or a weight that is not positive and finite, the
+    /// point ids are invalid, or there are more than `u32::MAX` points.
+    pub fn try_finish(self) -> Result<Supercluster<N>, SuperclusterError> {
+        self.try_finish_with_aggregator(&())
+    }
+
+    /// Convert a [SuperclusterBuilder] to a [Supercluster] by running hierarchical clustering,
+    /// accumulating properties onto each cluster with the provided [ClusterAggregator]. See
+    /// [`try_finish`][Self::try_finish].
+    pub fn try_finish_with_aggregator<A: ClusterAggregator>(
+        mut self,
+        aggregator: &A,
+    ) -> Result<Supercluster<N, A::Accumulator>, SuperclusterError> {
+        self.options.validate()?;
+        let [min_x, min_y, max_x, max_y] = self.projection.bounds();
+        if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite())
+            || min_x >= max_x
+            || min_y >= max_y
+        {
+            return Err(SuperclusterError::InvalidBounds(self.projection.bounds()));
+        }
+        if self.points.len() > u32::MAX as usize {
+            return Err(SuperclusterError::TooManyPoints(self.points.len()));
+        }
+        let order = self.id_order()?;
+        let (skipped, clamped) = self.check_points()?;
+        self.check_weights()?;
 
+        // Points are clustered in order of their ids, so that cluster ids don't depend on the
+        // order the points were added in
+        let projected = self.project_points();
         let mut data = Vec::with_capacity(self.points.len());
-        for (i, (lon, lat)) in self.points.iter().enumerate() {
-            data.push(ClusterData::new_geographic(
-                *lon,
-                *lat,
+        for (i, idx) in order.iter().enumerate() {
+            let mut item = ClusterData::new_point(
+                self.points[*idx],
+                projected[*idx],
                 ClusterId::new_source_id(i),
-            ));
+                self.weights[*idx],
+                self.categories[*idx],
+                // The order points were added in, rather than their position in id order
+                aggregator.map(*idx),
+            );
+
+            // Skipped points are kept as removed items, so that the positions of other points
+            // don't change
+            if skipped.binary_search(idx).is_ok() {
+                (item.x, item.y) = (N::zero(), N::zero());
+                item.num_points = 0;
+                item.weight = 0.0;
+            }
+            data.push(item);
+        }
+
+        let trees = HierarchyBuilder::new(self.options, aggregator).build(data);
+
+        let points = order.iter().map(|idx| self.points[*idx]).collect();
+        let ids = order.iter().map(|idx| self.ids[*idx]).collect();
+        let to_ids = |indices: Vec<usize>| {
+            let mut ids: Vec<u64> = indices.into_iter().map(|idx| self.ids[idx]).collect();
+            ids.sort();
+            ids
+        };
+        let mut supercluster =
+            Supercluster::new(points, ids, trees, self.options, self.projection.clone());
+        supercluster.coordinate_policy = self.coordinate_policy;
+        supercluster.skipped_points = to_ids(skipped);
+        supercluster.clamped_points = to_ids(clamped);
+        supercluster.index_cluster_ids();
+        Ok(supercluster)
+    }
+
+    /// Check that the point ids are valid and unique, returning the positions of the points in
+    /// order of their ids.
+    fn id_order(&self) -> Result<Vec<usize>, SuperclusterError> {
+        let mut order: Vec<usize> = (0..self.ids.len()).collect();
+        if self.ids.windows(2).any(|pair| pair[0] >= pair[1]) {
+            order.sort_by_key(|idx| self.ids[*idx]);
+        }
+
+        for pair in order.windows(2) {
+            if self.ids[pair[0]] == self.ids[pair[1]] {
+                return Err(SuperclusterError::DuplicatePointId(self.ids[pair[0]]));
+            }
+        }
+        if let Some(idx) = order.last() {
+            if self.ids[*idx] > ClusterId::MAX_POINT_ID {
+                return Err(SuperclusterError::InvalidPointId(self.ids[*idx]));
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Apply the [CoordinatePolicy] to points with invalid coordinates, returning the positions
+    /// of the points that were skipped and clamped.
+    fn check_points(&mut self) -> Result<(Vec<usize>, Vec<usize>), SuperclusterError> {
+        let mut skipped_points = vec![];
+        let mut clamped_points = vec![];
+
+        for (index, point) in self.points.iter_mut().enumerate() {
+            match self
+                .coordinate_policy
+                .apply(index, point, self.projection.as_ref())?
+            {
+                CoordinateAction::Keep => {}
+                CoordinateAction::Skip => skipped_points.push(index),
+                CoordinateAction::Clamp => clamped_points.push(index),
+            }
+        }
+
+        Ok((skipped_points, clamped_points))
+    }
+
+    /// Check that the weights of all points are positive and finite.
+    fn check_weights(&self) -> Result<(), SuperclusterError> {
+        match self
+            .weights
+            .iter()
+            .position(|weight| !(weight.is_finite() && *weight > 0.0))
+        {
+            Some(index) => Err(SuperclusterError::InvalidWeight {
+                index,
+                weight: self.weights[index],
+            }),
+            None => Ok(()),
         }
+    }
+
+    /// Project all input points to the unit square.
+    fn project_points(&self) -> Vec<(f64, f64)> {
+        #[cfg(feature = "rayon")]
+        let points = self.points.par_iter();
+
+        #[cfg(not(feature = "rayon"))]
+        let points = self.points.iter();
+
+        points
+            .map(|(x, y)| self.projection.project(*x, *y))
+            .collect()
+    }
+}
+
+/// Runs hierarchical clustering over full resolution data, one zoom level at a time.
+///
+/// This is shared between [SuperclusterBuilder::finish] and [Supercluster::compact].
+pub(crate) struct HierarchyBuilder<'a, A: ClusterAggregator> {
+    options: SuperclusterOptions,
+    aggregator: &'a A,
+}
+
+impl<'a, A: ClusterAggregator> HierarchyBuilder<'a, A> {
+    pub(crate) fn new(options: SuperclusterOptions, aggregator: &'a A) -> Self {
+        Self {
+            options,
+            aggregator,
+        }
+    }
+
+    /// Cluster the full resolution `data`, returning the tree at each zoom level.
+    ///
+    /// Points removed with [Supercluster::remove] may be included in `data`; they are skipped.
+    pub(crate) fn build<N: ClusterFloat>(
+        &self,
+        data: Vec<ClusterData<N, A::Accumulator>>,
+    ) -> HashMap<usize, TreeWithData<N, A::Accumulator>> {
+        let min_zoom = self.options.min_zoom;
+        let max_zoom = self.options.max_zoom;
 
-        let full_res_tree = TreeWithData::new(data, node_size);
+        let full_res_tree = TreeWithData::new(data, self.options.node_size);
 
         let mut trees = HashMap::with_capacity(max_zoom - min_zoom + 1);
         trees.insert(max_zoom + 1, full_res_tree);
@@ -78,113 +478,256 @@ impl SuperclusterBuilder {
             trees.insert(zoom, current);
         }
 
-        Supercluster::new(self.points, trees, self.options)
+        trees
     }
 
     /// Note: this mutates previous_tree's `data`.
     // This is derived from Supercluster._cluster in the original JS implementation
-    fn cluster(&self, previous_tree_with_data: &mut TreeWithData, zoom: usize) -> TreeWithData {
-        let radius = self.options.radius;
-        let extent = self.options.extent;
-        let min_points = self.options.min_points;
+    fn cluster<N: ClusterFloat>(
+        &self,
+        previous_tree_with_data: &mut TreeWithData<N, A::Accumulator>,
+        zoom: usize,
+    ) -> TreeWithData<N, A::Accumulator> {
+        #[cfg(feature = "rayon")]
+        let next_data = self.cluster_parallel(previous_tree_with_data, zoom);
+
+        #[cfg(not(feature = "rayon"))]
+        let next_data = self.cluster_serial(previous_tree_with_data, zoom);
+
+        TreeWithData::new(next_data, self.options.node_size)
+    }
 
-        let r = radius / (extent * usize::pow(2, zoom.try_into().unwrap()) as f64);
+    #[cfg_attr(feature = "rayon", allow(dead_code))]
+    fn cluster_serial<N: ClusterFloat>(
+        &self,
+        previous_tree_with_data: &mut TreeWithData<N, A::Accumulator>,
+        zoom: usize,
+    ) -> Vec<ClusterData<N, A::Accumulator>> {
+        let r = N::from_f64(self.options.zoom_radius(zoom));
         let data = &mut previous_tree_with_data.data;
-        let previous_tree = &previous_tree_with_data.tree;
+        let Some(previous_tree) = previous_tree_with_data
+            .tree
+            .as_ref()
+            .map(|tree| tree.as_ref())
+        else {
+            return vec![];
+        };
         let mut next_data = vec![];
 
         // loop through each point
         for i in 0..data.len() {
             // if we've already visited the point at this zoom level, skip it
-            if data[i].zoom.is_some_and(|z| z <= zoom) {
+            if data[i].zoom.is_some_and(|z| z <= zoom) || data[i].is_removed() {
                 continue;
             }
 
-            data[i].zoom = Some(zoom);
-
             // find all nearby points
-            let x = data[i].x;
-            let y = data[i].y;
-            let neighbor_ids = previous_tree.as_ref().within(x, y, r);
-
-            let num_points_origin = data[i].num_points;
-            let mut num_points = num_points_origin;
-
-            // count the number of points in a potential cluster
-            for neighbor_id in &neighbor_ids {
-                // filter out neighbors that are already processed
-
-                // NOTE: in the original implementation, it checked
-                // `if (data[k + OFFSET_ZOOM] > zoom)`
-                // But note that the `OFFSET_ZOOM` in the data array was **initialized** to
-                // `Infinity`. Therefore, this should also be true when `.zoom.is_none()`.
-
-                if data[*neighbor_id].zoom.is_none()
-                    || data[*neighbor_id].zoom.is_some_and(|z| z > zoom)
-                {
-                    num_points += data[*neighbor_id].num_points;
+            let neighbor_ids = previous_tree.within(data[i].x, data[i].y, r);
+
+            self.merge_neighbors(data, &mut next_data, i, neighbor_ids, zoom);
+        }
+
+        next_data
+    }
+
+    /// Equivalent to [`cluster_serial`][Self::cluster_serial], but with neighbor searches run in
+    /// parallel.
+    ///
+    /// Neighbor searches only depend on the tree and the coordinates of each point, which don't
+    /// change during clustering, so they can be run ahead of the greedy merge. The merge itself
+    /// depends on the order in which points are visited, so it stays sequential.
+    ///
+    /// A search is wasted if an earlier merge in the same batch absorbs its point. This happens
+    /// often in dense areas, where it is also the most expensive, so batches start small and only
+    /// grow while few searches are wasted and the neighbor lists stay within a memory budget.
+    #[cfg(feature = "rayon")]
+    fn cluster_parallel<N: ClusterFloat>(
+        &self,
+        previous_tree_with_data: &mut TreeWithData<N, A::Accumulator>,
+        zoom: usize,
+    ) -> Vec<ClusterData<N, A::Accumulator>> {
+        let r = N::from_f64(self.options.zoom_radius(zoom));
+        let data = &mut previous_tree_with_data.data;
+        let Some(previous_tree) = previous_tree_with_data
+            .tree
+            .as_ref()
+            .map(|tree| tree.as_ref())
+        else {
+            return vec![];
+        };
+        let mut next_data = vec![];
+
+        let min_batch_size = rayon::current_num_threads();
+        let mut batch_size = min_batch_size;
+        let mut batch_start = 0;
+        while batch_start < data.len() {
+            let batch_end = usize::min(batch_start + batch_size, data.len());
+
+            // Points that were already visited don't need a search
+            let queries: Vec<Option<(N, N)>> = data[batch_start..batch_end]
+                .iter()
+                .map(|item| {
+                    if item.zoom.is_some_and(|z| z <= zoom) || item.is_removed() {
+                        None
+                    } else {
+                        Some((item.x, item.y))
+                    }
+                })
+                .collect();
+            let neighbors: Vec<Option<Vec<usize>>> = queries
+                .into_par_iter()
+                .map(|query| query.map(|(x, y)| previous_tree.within(x, y, r)))
+                .collect();
+
+            let mut num_searches = 0;
+            let mut num_wasted = 0;
+            let mut num_ids = 0;
+            for (i, neighbor_ids) in (batch_start..batch_end).zip(neighbors) {
+                let Some(neighbor_ids) = neighbor_ids else {
+                    continue;
+                };
+                num_searches += 1;
+                num_ids += neighbor_ids.len();
+
+                // the point may have been visited since the batch was searched
+                if data[i].zoom.is_some_and(|z| z <= zoom) {
+                    num_wasted += 1;
+                    continue;
                 }
+
+                self.merge_neighbors(data, &mut next_data, i, neighbor_ids, zoom);
             }
 
-            // if there were neighbors to merge, and there are enough points to form a cluster
-            if num_points > num_points_origin && num_points >= min_points {
-                let mut wx = x * num_points_origin as f64;
-                let mut wy = y * num_points_origin as f64;
+            batch_start = batch_end;
+            batch_size = if num_wasted * 4 > num_searches || num_ids > MAX_NEIGHBOR_BATCH_IDS {
+                usize::max(batch_size / 2, min_batch_size)
+            } else if num_ids * 2 <= MAX_NEIGHBOR_BATCH_IDS {
+                usize::min(batch_size * 2, MAX_NEIGHBOR_BATCH_SIZE)
+            } else {
+                batch_size
+            };
+        }
 
-                // encode both zoom and point index on which the cluster originated -- offset by total length of features
-                let id = ClusterId::new(i, zoom, self.points.len());
+        next_data
+    }
 
-                for neighbor_id in neighbor_ids {
-                    if data[neighbor_id].zoom.is_some_and(|z| z <= zoom) {
-                        continue;
-                    }
+    /// Merge the point at index `i` with its unvisited neighbors of the same category, if there are
+    /// enough of them to form a cluster, pushing the result onto `next_data`.
+    fn merge_neighbors<N: ClusterFloat>(
+        &self,
+        data: &mut [ClusterData<N, A::Accumulator>],
+        next_data: &mut Vec<ClusterData<N, A::Accumulator>>,
+        i: usize,
+        mut neighbor_ids: Vec<usize>,
+        zoom: usize,
+    ) {
+        let min_points = self.options.min_points;
 
-                    // save the zoom (so it doesn't get processed twice)
-                    data[neighbor_id].zoom = Some(zoom);
+        data[i].zoom = Some(zoom);
+
+        // only points of the same category can be merged
+        let category = data[i].category;
+        neighbor_ids.retain(|neighbor_id| {
+            let neighbor = &data[*neighbor_id];
+            !neighbor.is_removed() && neighbor.category == category
+        });
+
+        let x = data[i].x;
+        let y = data[i].y;
+
+        let num_points_origin = data[i].num_points;
+        let mut num_points = num_points_origin;
+        let weight_origin = data[i].weight;
+        let mut weight = weight_origin;
 
-                    let num_points2 = data[neighbor_id].num_points as f64;
+        // count the number of points in a potential cluster
+        for neighbor_id in &neighbor_ids {
+            // filter out neighbors that are already processed
 
-                    // accumulate coordinates for calculating weighted center
-                    wx += data[neighbor_id].x * num_points2;
-                    wy += data[neighbor_id].y * num_points2;
+            // NOTE: in the original implementation, it checked
+            // `if (data[k + OFFSET_ZOOM] > zoom)`
+            // But note that the `OFFSET_ZOOM` in the data array was **initialized** to
+            // `Infinity`. Therefore, this should also be true when `.zoom.is_none()`.
 
-                    data[neighbor_id].parent_id = Some(id);
+            if data[*neighbor_id].zoom.is_none()
+                || data[*neighbor_id].zoom.is_some_and(|z| z > zoom)
+            {
+                num_points += data[*neighbor_id].num_points;
+                weight += data[*neighbor_id].weight;
+            }
+        }
+
+        // if there were neighbors to merge, and there is enough weight to form a cluster
+        if num_points > num_points_origin && weight >= min_points as f64 {
+            let mut wx = x.as_f64() * weight_origin;
+            let mut wy = y.as_f64() * weight_origin;
+            let mut bounds = data[i].bounds;
+            let mut min_point = data[i].min_point;
+            let mut properties = data[i].properties.clone();
+
+            // the id has the cluster bit set, the zoom the origin point is indexed at (zoom + 1)
+            // in the next 6 bits, and the index of the origin point within that zoom in the
+            // lower 57 bits. Query results replace the index with the smallest point id.
+            let id = ClusterId::new(i as u64, zoom);
+
+            for neighbor_id in neighbor_ids {
+                if data[neighbor_id].zoom.is_some_and(|z| z <= zoom) {
+                    continue;
                 }
 
-                data[i].parent_id = Some(id);
+                // save the zoom (so it doesn't get processed twice)
+                data[neighbor_id].zoom = Some(zoom);
 
-                next_data.push(ClusterData {
-                    x: wx / num_points as f64,
-                    y: wy / num_points as f64,
-                    zoom: None,
-                    source_id: id,
-                    parent_id: None,
-                    num_points,
-                });
-            } else {
-                // left points as unclustered
-                next_data.push(data[i].clone());
+                let weight2 = data[neighbor_id].weight;
+
+                // accumulate coordinates for calculating weighted center
+                wx += data[neighbor_id].x.as_f64() * weight2;
+                wy += data[neighbor_id].y.as_f64() * weight2;
+                extend_bounds(&mut bounds, &data[neighbor_id].bounds);
+                min_point = min_point.min(data[neighbor_id].min_point);
+                self.aggregator
+                    .reduce(&mut properties, &data[neighbor_id].properties);
+
+                data[neighbor_id].parent_id = Some(id);
+            }
 
-                if num_points > 1 {
-                    for neighbor_id in neighbor_ids {
-                        if data[neighbor_id].zoom.is_some_and(|z| z <= zoom) {
-                            continue;
-                        }
+            data[i].parent_id = Some(id);
 
-                        data[neighbor_id].zoom = Some(zoom);
+            next_data.push(ClusterData {
+                x: N::from_f64(wx / weight),
+                y: N::from_f64(wy / weight),
+                zoom: None,
+                source_id: id,
+                parent_id: None,
+                num_points,
+                min_point,
+                weight,
+                category,
+                bounds,
+                properties,
+            });
+        } else {
+            // left points as unclustered
+            next_data.push(data[i].clone());
 
-                        next_data.push(data[neighbor_id].clone());
+            if num_points > 1 {
+                for neighbor_id in neighbor_ids {
+                    if data[neighbor_id].zoom.is_some_and(|z| z <= zoom) {
+                        continue;
                     }
+
+                    data[neighbor_id].zoom = Some(zoom);
+
+                    next_data.push(data[neighbor_id].clone());
                 }
             }
         }
-
-        TreeWithData::new(next_data, self.options.node_size)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::test::index::{build_index, build_index_with_aggregator, PointSum};
     use crate::test::load_fixture::load_places;
 
     use super::*;
@@ -199,4 +742,436 @@ mod test {
         let _supercluster = builder.finish();
         // dbg!(supercluster);
     }
+
+    #[test]
+    fn test_builder_f32() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::<f32>::new_with_coord_type(
+            coords.len(),
+            SuperclusterOptions::default(),
+        );
+        for coord in &coords {
+            builder.add(coord[0], coord[1]);
+        }
+        let supercluster = builder.finish();
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let total: usize = clusters.iter().map(|cluster| cluster.count()).sum();
+            assert_eq!(total, coords.len());
+        }
+    }
+
+    #[test]
+    fn test_weighted() {
+        let options = SuperclusterOptions::default().with_min_points(5);
+        let mut builder = SuperclusterBuilder::new_with_options(4, options)
+            .with_planar_extent(0.0, 0.0, 100.0, 100.0);
+        builder.add_weighted(10.0, 10.0, 4.0);
+        builder.add(11.0, 10.0);
+        builder.add(80.0, 80.0);
+        builder.add(81.0, 80.0);
+        let supercluster = builder.finish();
+
+        let mut clusters = supercluster.get_clusters(0.0, 0.0, 100.0, 100.0, 0);
+        clusters.sort_by(|a, b| a.x().total_cmp(&b.x()));
+        assert_eq!(clusters.len(), 3);
+
+        // The heavy point reaches the minimum weight and pulls the center towards it
+        let cluster = &clusters[0];
+        assert!(cluster.is_cluster());
+        assert_eq!((cluster.count(), cluster.weight()), (2, 5.0));
+        assert!((cluster.x() - 10.2).abs() < 1e-9);
+        assert!((cluster.y() - 10.0).abs() < 1e-9);
+
+        // Two unit points don't weigh enough to form a cluster
+        assert!(!clusters[1].is_cluster());
+        assert_eq!(clusters[1].weight(), 1.0);
+    }
+
+    #[test]
+    fn test_unweighted() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in clusters {
+                assert_eq!(cluster.weight(), cluster.count() as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_weight() {
+        for weight in [f64::NAN, f64::INFINITY, 0.0, -1.0] {
+            let mut builder = SuperclusterBuilder::new(2);
+            builder.add(0.0, 0.0);
+            builder.add_weighted(0.0, 0.0, weight);
+            assert!(matches!(
+                builder.try_finish(),
+                Err(SuperclusterError::InvalidWeight { index: 1, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_try_finish() {
+        let try_finish = |options: SuperclusterOptions, x: f64| {
+            let mut builder = SuperclusterBuilder::new_with_options(2, options);
+            builder.add(0.0, 0.0);
+            builder.add(x, 0.0);
+            builder.try_finish()
+        };
+        let options = SuperclusterOptions::default();
+
+        assert!(try_finish(options, 1.0).is_ok());
+        assert!(matches!(
+            try_finish(options.with_min_zoom(5).with_max_zoom(4), 1.0),
+            Err(SuperclusterError::InvalidZoomRange {
+                min_zoom: 5,
+                max_zoom: 4
+            })
+        ));
+        let max_zoom = SuperclusterOptions::MAX_ZOOM;
+        let supercluster = try_finish(options.with_max_zoom(max_zoom), 0.0).unwrap();
+        let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, max_zoom);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(
+            clusters[0].id().origin_zoom(),
+            Some(ClusterId::MAX_ORIGIN_ZOOM)
+        );
+        assert_eq!(
+            supercluster.get_children(clusters[0].id()).unwrap().len(),
+            2
+        );
+        assert!(matches!(
+            try_finish(options.with_max_zoom(max_zoom + 1), 1.0),
+            Err(SuperclusterError::MaxZoomTooLarge(63))
+        ));
+        assert!(matches!(
+            try_finish(options.with_radius(0.0), 1.0),
+            Err(SuperclusterError::InvalidRadius(_))
+        ));
+        assert!(matches!(
+            try_finish(options.with_extent(f64::INFINITY), 1.0),
+            Err(SuperclusterError::InvalidExtent(_))
+        ));
+        assert!(matches!(
+            try_finish(options.with_node_size(1), 1.0),
+            Err(SuperclusterError::InvalidNodeSize(1))
+        ));
+        assert!(matches!(
+            try_finish(options, f64::NAN),
+            Err(SuperclusterError::InvalidCoordinate { index: 1, .. })
+        ));
+
+        for extent in [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 100.0, f64::NAN],
+            [0.0, 0.0, f64::INFINITY, 100.0],
+        ] {
+            let [min_x, min_y, max_x, max_y] = extent;
+            let builder =
+                SuperclusterBuilder::new(0).with_planar_extent(min_x, min_y, max_x, max_y);
+            assert!(matches!(
+                builder.try_finish(),
+                Err(SuperclusterError::InvalidBounds(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_coordinate_policy() {
+        let build = |policy: CoordinatePolicy| {
+            let mut builder = SuperclusterBuilder::new(5).with_coordinate_policy(policy);
+            builder.add(10.0, 10.0);
+            builder.add(f64::NAN, 10.0);
+            builder.add(10.0, 95.0);
+            builder.add(-200.0, 10.0);
+            builder.add(10.0, 10.0);
+            builder.try_finish()
+        };
+
+        assert!(matches!(
+            build(CoordinatePolicy::Reject),
+            Err(SuperclusterError::InvalidCoordinate { index: 1, .. })
+        ));
+        assert!(matches!(
+            build(CoordinatePolicy::Clamp),
+            Err(SuperclusterError::InvalidCoordinate { index: 1, .. })
+        ));
+
+        let supercluster = build(CoordinatePolicy::Skip).unwrap();
+        assert_eq!(supercluster.skipped_points(), &[1, 2, 3]);
+        assert!(supercluster.clamped_points().is_empty());
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let total: usize = clusters.iter().map(|cluster| cluster.count()).sum();
+            assert_eq!(total, 2, "zoom {zoom}");
+        }
+        let leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        let mut ids: Vec<usize> = leaves.iter().map(|leaf| leaf.id().as_usize()).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 4]);
+
+        let mut builder = SuperclusterBuilder::new(3);
+        builder.add(10.0, 10.0);
+        builder.add(10.0, 95.0);
+        builder.add(-200.0, 10.0);
+        let supercluster = builder.finish();
+        assert_eq!(supercluster.clamped_points(), &[1, 2]);
+        let mut leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        leaves.sort_by_key(|leaf| leaf.id().as_usize());
+        let coords: Vec<(f64, f64)> = leaves.iter().map(|leaf| (leaf.x(), leaf.y())).collect();
+        assert_eq!(coords, vec![(10.0, 10.0), (10.0, 90.0), (160.0, 10.0)]);
+
+        // Without wrapping, x is clamped too
+        let mut builder = SuperclusterBuilder::new(2).with_planar_extent(0.0, 0.0, 100.0, 100.0);
+        builder.add(10.0, 10.0);
+        builder.add(-20.0, 120.0);
+        let supercluster = builder.finish();
+        assert_eq!(supercluster.clamped_points(), &[1]);
+        let leaves = supercluster.get_clusters(0.0, 0.0, 100.0, 100.0, 17);
+        assert!(leaves
+            .iter()
+            .any(|leaf| (leaf.x(), leaf.y()) == (0.0, 100.0)));
+    }
+
+    #[test]
+    fn test_categories() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        for (i, coord) in coords.iter().enumerate() {
+            builder.add_with_category(coord[0], coord[1], (i % 3) as u32);
+        }
+        let supercluster = builder.finish();
+
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let total: usize = clusters.iter().map(|cluster| cluster.count()).sum();
+            assert_eq!(total, coords.len());
+
+            for cluster in clusters.iter().filter(|cluster| cluster.is_cluster()) {
+                let leaves = supercluster
+                    .get_leaves(cluster.id(), Some(usize::MAX), None)
+                    .unwrap();
+                for leaf in leaves {
+                    assert_eq!(leaf.category(), cluster.category(), "zoom {zoom}");
+                    assert_eq!(leaf.id().as_usize() % 3, cluster.category() as usize);
+                }
+            }
+        }
+
+        // Coincident points of different categories are never merged
+        let mut builder = SuperclusterBuilder::new(4);
+        builder.add_with_category(10.0, 10.0, 1);
+        builder.add_with_category(10.0, 10.0, 1);
+        builder.add_with_category(10.0, 10.0, 2);
+        builder.add(10.0, 10.0);
+        let supercluster = builder.finish();
+        let mut clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 0);
+        clusters.sort_by_key(|cluster| cluster.category());
+        let clusters: Vec<_> = clusters
+            .iter()
+            .map(|cluster| (cluster.category(), cluster.count()))
+            .collect();
+        assert_eq!(clusters, vec![(0, 1), (1, 2), (2, 1)]);
+
+        let tile = supercluster.get_tile(0, 0, 0);
+        assert!(tile
+            .iter()
+            .any(|feature| feature.category() == 1 && feature.is_cluster()));
+    }
+
+    #[test]
+    fn test_cluster_bounds() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        for zoom in 0..=17 {
+            for cluster in supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom) {
+                if !cluster.is_cluster() {
+                    let [x, y] = [cluster.x(), cluster.y()];
+                    assert_eq!(cluster.bounds(), [x, y, x, y]);
+                    continue;
+                }
+
+                let leaves = supercluster
+                    .get_leaves(cluster.id(), Some(usize::MAX), None)
+                    .unwrap();
+                let bounds = [
+                    leaves
+                        .iter()
+                        .map(|leaf| leaf.x())
+                        .fold(f64::INFINITY, f64::min),
+                    leaves
+                        .iter()
+                        .map(|leaf| leaf.y())
+                        .fold(f64::INFINITY, f64::min),
+                    leaves
+                        .iter()
+                        .map(|leaf| leaf.x())
+                        .fold(f64::NEG_INFINITY, f64::max),
+                    leaves
+                        .iter()
+                        .map(|leaf| leaf.y())
+                        .fold(f64::NEG_INFINITY, f64::max),
+                ];
+                assert_eq!(cluster.bounds(), bounds, "zoom {zoom}");
+                assert_eq!(
+                    supercluster.get_cluster_bounds(cluster.id()).unwrap(),
+                    bounds
+                );
+            }
+        }
+
+        assert!(supercluster
+            .get_cluster_bounds(ClusterId::new_source_id(0))
+            .is_err());
+        assert!(supercluster
+            .get_cluster_bounds(ClusterId::new(1 << 40, 3))
+            .is_err());
+    }
+
+    #[test]
+    fn test_point_ids() {
+        let coords = load_places();
+        let point_id = |i: usize| 1000 + 7 * i as u64;
+
+        let mut forward = SuperclusterBuilder::new(coords.len());
+        for (i, coord) in coords.iter().enumerate() {
+            assert_eq!(forward.add_with_id(coord[0], coord[1], point_id(i)), i);
+        }
+        let forward = forward.finish();
+
+        let mut reversed = SuperclusterBuilder::new(coords.len());
+        for (i, coord) in coords.iter().enumerate().rev() {
+            reversed.add_with_id(coord[0], coord[1], point_id(i));
+        }
+        let reversed = reversed.finish();
+
+        // Cluster ids do not depend on the order points were added in
+        let describe = |supercluster: &Supercluster, zoom| {
+            let mut clusters: Vec<_> = supercluster
+                .get_clusters(-180.0, -90.0, 180.0, 90.0, zoom)
+                .iter()
+                .map(|cluster| (cluster.id().as_u64(), cluster.count()))
+                .collect();
+            clusters.sort();
+            clusters
+        };
+        for zoom in 0..=17 {
+            assert_eq!(describe(&forward, zoom), describe(&reversed, zoom));
+        }
+
+        // Nor on points that are not in the cluster, even if they have a smaller id
+        let mut extended = SuperclusterBuilder::new(coords.len() + 1);
+        extended.add_with_id(0.0, -85.0, 0);
+        for (i, coord) in coords.iter().enumerate() {
+            extended.add_with_id(coord[0], coord[1], point_id(i));
+        }
+        let extended = extended.finish();
+        let leaves = |supercluster: &Supercluster, zoom| {
+            let mut clusters = HashMap::new();
+            for cluster in supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom) {
+                if cluster.is_cluster() {
+                    let mut ids: Vec<u64> = supercluster
+                        .get_leaves(cluster.id(), Some(usize::MAX), None)
+                        .unwrap()
+                        .iter()
+                        .map(|leaf| leaf.id().as_u64())
+                        .collect();
+                    ids.sort();
+                    clusters.insert(ids, cluster.id());
+                }
+            }
+            clusters
+        };
+        let mut unchanged = 0;
+        for zoom in 0..=17 {
+            let before = leaves(&forward, zoom);
+            for (ids, id) in leaves(&extended, zoom) {
+                if let Some(before_id) = before.get(&ids) {
+                    assert_eq!(id, *before_id, "zoom {zoom}");
+                    unchanged += 1;
+                }
+            }
+        }
+        assert!(unchanged > 0);
+
+        // Leaves have the id of their point
+        let mut ids: Vec<u64> = forward
+            .get_clusters(-180.0, -90.0, 180.0, 90.0, 17)
+            .iter()
+            .map(|leaf| leaf.id().point_id().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, (0..coords.len()).map(point_id).collect::<Vec<_>>());
+        for leaf in forward.get_clusters(-180.0, -90.0, 180.0, 90.0, 17) {
+            let i = ((leaf.id().as_u64() - 1000) / 7) as usize;
+            assert_eq!((leaf.x(), leaf.y()), (coords[i][0], coords[i][1]));
+        }
+
+        let mut builder = SuperclusterBuilder::new(2);
+        builder.add_with_id(10.0, 10.0, 5);
+        builder.add_with_id(20.0, 20.0, 5);
+        assert!(matches!(
+            builder.try_finish(),
+            Err(SuperclusterError::DuplicatePointId(5))
+        ));
+
+        let mut builder = SuperclusterBuilder::new(1);
+        builder.add_with_id(10.0, 10.0, u64::MAX);
+        assert!(matches!(
+            builder.try_finish(),
+            Err(SuperclusterError::InvalidPointId(u64::MAX))
+        ));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        for coord in coords {
+            builder.add(coord[0], coord[1]);
+        }
+
+        let data = builder
+            .project_points()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, y))| {
+                ClusterData::<f64>::new_projected(x, y, ClusterId::new_source_id(i), ())
+            })
+            .collect();
+        let options = builder.options;
+        let hierarchy = HierarchyBuilder::new(options, &());
+        let mut serial_tree = TreeWithData::new(data, options.node_size);
+        let mut parallel_tree = serial_tree.clone();
+
+        for zoom in (0..=options.max_zoom).rev() {
+            let serial_data = hierarchy.cluster_serial(&mut serial_tree, zoom);
+            let parallel_data = hierarchy.cluster_parallel(&mut parallel_tree, zoom);
+            assert_eq!(serial_data.len(), parallel_data.len());
+            for (a, b) in serial_data.iter().zip(parallel_data.iter()) {
+                assert_eq!((a.x, a.y, a.source_id), (b.x, b.y, b.source_id));
+                assert_eq!((a.num_points, a.parent_id), (b.num_points, b.parent_id));
+            }
+
+            serial_tree = TreeWithData::new(serial_data, options.node_size);
+            parallel_tree = TreeWithData::new(parallel_data, options.node_size);
+        }
+    }
+
+    #[test]
+    fn test_aggregator() {
+        let coords = load_places();
+        let supercluster = build_index_with_aggregator(&coords, &PointSum);
+        for zoom in 0..=16 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in clusters {
+                assert_eq!(*cluster.properties() as usize, cluster.count());
+            }
+        }
+    }
 }
@@ -1,54 +1,196 @@
+use crate::r#type::ClusterFloat;
 use crate::util::{latitude_to_y, longitude_to_x, x_to_longitude, y_to_latitude};
 
-// encode both zoom and point index on which the cluster originated -- offset by total length of
-// features
+/// The id of a point or cluster in a [Supercluster][crate::Supercluster].
+///
+/// Ids are 64-bit integers, independent of the number of points in the index and of the target's
+/// pointer width. The highest bit distinguishes the ids of input points from the ids of clusters:
+///
+/// - Input points: bit 63 is `0`, and the other bits are the id of the point, at most
+///   [`MAX_POINT_ID`][Self::MAX_POINT_ID]. This is the id given to
+///   [`SuperclusterBuilder::add_with_id`][crate::SuperclusterBuilder::add_with_id], or the index
+///   of the point in the order it was added.
+/// - Clusters: bit 63 is `1`, bits 57 to 62 are the origin zoom, and bits 0 to 56 are the
+///   smallest id among the points of the cluster.
+///
+/// A cluster formed at zoom `z` has the origin zoom `z + 1`, the zoom of the point or cluster it
+/// was grown from. Its id only depends on its points, not on their positions or on other points,
+/// so it stays the same across rebuilds as long as the cluster has the same points, even if other
+/// points are added or removed.
+///
+/// The origin zoom is at most [`MAX_ORIGIN_ZOOM`][Self::MAX_ORIGIN_ZOOM], which limits
+/// [`max_zoom`][crate::SuperclusterOptions::max_zoom] to
+/// [`SuperclusterOptions::MAX_ZOOM`][crate::SuperclusterOptions::MAX_ZOOM].
 #[repr(transparent)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ClusterId(usize);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClusterId(u64);
 
-// Note: I think like many of mourner's projects, here the ID can have multiple meanings depending
-// on whether it's a leaf or an internal node of the tree
-//
-// Thus we have multiple constructors
+// Within the index, items refer to input points by their position rather than their id, and
+// clusters by the index of their origin item within the origin zoom rather than by the smallest id
+// among their points. These are replaced by ids in query results.
 impl ClusterId {
-    pub fn new(i: usize, zoom: usize, length: usize) -> Self {
-        let id = (i << 5) + (zoom + 1) + length;
-        Self(id)
+    /// The bit that is set in the ids of clusters.
+    const CLUSTER_BIT: u64 = 1 << 63;
+
+    /// The number of low bits storing the smallest point id of a cluster.
+    const MIN_POINT_ID_BITS: u32 = 57;
+
+    /// The largest id of an input point, which must fit in the ids of the clusters grown from it.
+    ///
+    /// This is one less than the largest value of the bit field, so that `u64::MAX` is never a
+    /// valid id.
+    pub const MAX_POINT_ID: u64 = (1 << Self::MIN_POINT_ID_BITS) - 2;
+
+    /// The largest origin zoom of a cluster.
+    pub const MAX_ORIGIN_ZOOM: usize = 63;
+
+    /// The id of a cluster formed at `zoom`, whose smallest point id is `min_point_id`.
+    ///
+    /// ## Panics
+    ///
+    /// If `min_point_id` is greater than [`MAX_POINT_ID`][Self::MAX_POINT_ID] or `zoom + 1` is
+    /// greater than [`MAX_ORIGIN_ZOOM`][Self::MAX_ORIGIN_ZOOM].
+    pub fn new(min_point_id: u64, zoom: usize) -> Self {
+        assert!(
+            min_point_id <= Self::MAX_POINT_ID,
+            "point id {min_point_id} is too large"
+        );
+        assert!(zoom < Self::MAX_ORIGIN_ZOOM, "zoom {zoom} is too large");
+        Self(Self::CLUSTER_BIT | ((zoom as u64 + 1) << Self::MIN_POINT_ID_BITS) | min_point_id)
     }
 
+    /// The id of the input point with the given id.
+    ///
+    /// ## Panics
+    ///
+    /// If `id` is greater than [`MAX_POINT_ID`][Self::MAX_POINT_ID].
     pub fn new_source_id(id: usize) -> Self {
-        Self(id)
+        assert!(
+            id as u64 <= Self::MAX_POINT_ID,
+            "point id {id} is too large"
+        );
+        Self(id as u64)
     }
 
-    pub fn as_usize(self) -> usize {
+    /// The raw value of this id.
+    pub fn as_u64(self) -> u64 {
         self.0
     }
 
+    /// The raw value of this id, as a `usize`.
+    ///
+    /// For an input point, this is its index.
+    ///
+    /// ## Panics
+    ///
+    /// If the value does not fit in a `usize`, as for the ids of clusters on 32-bit targets.
+    pub fn as_usize(self) -> usize {
+        usize::try_from(self.0).expect("cluster id does not fit in usize")
+    }
+
+    /// Whether this is the id of a cluster, rather than of an input point.
+    pub fn is_cluster(&self) -> bool {
+        self.0 & Self::CLUSTER_BIT != 0
+    }
+
+    /// The id of the input point, if this is not the id of a cluster.
+    pub fn point_id(&self) -> Option<u64> {
+        (!self.is_cluster()).then_some(self.0)
+    }
+
+    /// The zoom of the point or cluster this cluster was grown from, which is one more than the
+    /// zoom at which the cluster was formed. `None` if this is not the id of a cluster.
+    pub fn origin_zoom(&self) -> Option<usize> {
+        self.is_cluster().then(|| self.get_origin_zoom())
+    }
+
+    /// The smallest id among the points of this cluster. `None` if this is not the id of a
+    /// cluster.
+    pub fn min_point_id(&self) -> Option<u64> {
+        self.is_cluster().then(|| self.low_bits())
+    }
+
+    /// Decode this id into the id of an input point, or the origin zoom and smallest point id of
+    /// a cluster.
+    ///
+    /// ```
+    /// use supercluster_rs::{ClusterId, ClusterIdKind};
+    ///
+    /// let id = ClusterId::new(12, 3);
+    /// assert_eq!(
+    ///     id.decode(),
+    ///     ClusterIdKind::Cluster {
+    ///         origin_zoom: 4,
+    ///         min_point_id: 12
+    ///     }
+    /// );
+    /// ```
+    pub fn decode(&self) -> ClusterIdKind {
+        if self.is_cluster() {
+            ClusterIdKind::Cluster {
+                origin_zoom: self.get_origin_zoom(),
+                min_point_id: self.low_bits(),
+            }
+        } else {
+            ClusterIdKind::Leaf { point_id: self.0 }
+        }
+    }
+
+    /// The value of the bits below the origin zoom.
+    fn low_bits(&self) -> u64 {
+        self.0 & ((1 << Self::MIN_POINT_ID_BITS) - 1)
+    }
+
     /// get index of the point from which the cluster originated
-    // Note: I _think_ this doesn't return a ClusterId
-    pub(crate) fn get_origin_idx(&self, length: usize) -> usize {
-        (self.0 - length) >> 5
+    pub(crate) fn get_origin_idx(&self) -> usize {
+        self.low_bits() as usize
     }
 
     /// get zoom of the point from which the cluster originated
-    pub(crate) fn get_origin_zoom(&self, length: usize) -> usize {
-        (self.0 - length) % 32
+    pub(crate) fn get_origin_zoom(&self) -> usize {
+        ((self.0 & !Self::CLUSTER_BIT) >> Self::MIN_POINT_ID_BITS) as usize
+    }
+}
+
+/// A decoded [ClusterId], as returned by [`ClusterId::decode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClusterIdKind {
+    /// An input point.
+    Leaf {
+        /// The id of the point. See [ClusterId].
+        point_id: u64,
+    },
+
+    /// A cluster.
+    Cluster {
+        /// The zoom of the point or cluster this cluster was grown from, which is one more than
+        /// the zoom at which the cluster was formed.
+        origin_zoom: usize,
+
+        /// The smallest id among the points of the cluster. See [ClusterId].
+        min_point_id: u64,
+    },
+}
+
+impl From<u64> for ClusterId {
+    fn from(value: u64) -> Self {
+        Self(value)
     }
 }
 
-impl From<ClusterId> for usize {
+impl From<ClusterId> for u64 {
     fn from(value: ClusterId) -> Self {
         value.0
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct ClusterData {
+pub struct ClusterData<N: ClusterFloat = f64, P = ()> {
     /// projected point x
-    pub(crate) x: f64,
+    pub(crate) x: N,
 
     /// projected point y
-    pub(crate) y: f64,
+    pub(crate) y: N,
 
     /// The last zoom the point was processed at
     pub(crate) zoom: Option<usize>,
@@ -59,54 +201,98 @@ pub struct ClusterData {
     // parent cluster id
     pub(crate) parent_id: Option<ClusterId>,
 
-    // number of points in a cluster
+    // number of points in a cluster, or 0 if the point was removed
     pub(crate) num_points: usize,
+
+    // position of the point with the smallest id in a cluster, or of the point itself
+    pub(crate) min_point: usize,
+
+    // total weight of the points in a cluster
+    pub(crate) weight: f64,
+
+    // category shared by the points in a cluster
+    pub(crate) category: u32,
+
+    // bounding box of the points in a cluster, in the user's original coordinate system
+    pub(crate) bounds: [f64; 4],
+
+    // aggregated properties of the points in a cluster
+    pub(crate) properties: P,
 }
 
-impl ClusterData {
+impl<N: ClusterFloat, P> ClusterData<N, P> {
     /// Create a new object from longitude-latitude x and y values
-    pub fn new_geographic(lon: f64, lat: f64, source_id: ClusterId) -> Self {
-        let x = longitude_to_x(lon);
-        let y = latitude_to_y(lat);
-        Self::new_projected(x, y, source_id)
+    pub fn new_geographic(lon: f64, lat: f64, source_id: ClusterId, properties: P) -> Self {
+        let projected = (longitude_to_x(lon), latitude_to_y(lat));
+        Self::new_point((lon, lat), projected, source_id, 1.0, 0, properties)
+    }
+
+    /// Create a new object from x and y values already projected to the unit square with
+    /// [`WebMercator`][crate::WebMercator], whose bounds are unprojected back to longitude and
+    /// latitude
+    pub fn new_projected(x: f64, y: f64, source_id: ClusterId, properties: P) -> Self {
+        let point = (x_to_longitude(x), y_to_latitude(y));
+        Self::new_point(point, (x, y), source_id, 1.0, 0, properties)
     }
 
-    /// Create a new object from spherical mercator x and y values
-    pub fn new_projected(x: f64, y: f64, source_id: ClusterId) -> Self {
+    /// Create a new object from a point in the user's original coordinate system and its
+    /// projection, with the given weight and category
+    pub(crate) fn new_point(
+        point: (f64, f64),
+        (x, y): (f64, f64),
+        source_id: ClusterId,
+        weight: f64,
+        category: u32,
+        properties: P,
+    ) -> Self {
         Self {
-            x,
-            y,
+            x: N::from_f64(x),
+            y: N::from_f64(y),
             zoom: None,
             source_id,
             parent_id: None,
             num_points: 1,
+            min_point: source_id.as_u64() as usize,
+            weight,
+            category,
+            bounds: [point.0, point.1, point.0, point.1],
+            properties,
         }
     }
 
-    /// The x value of this point in Spherical Mercator projection
-    pub fn x(&self) -> f64 {
+    /// The x value of this point, in projected coordinates of the index's projection
+    pub fn x(&self) -> N {
         self.x
     }
 
-    /// The y value of this point in Spherical Mercator projection
-    pub fn y(&self) -> f64 {
+    /// The y value of this point, in projected coordinates of the index's projection
+    pub fn y(&self) -> N {
         self.y
     }
+
+    /// The aggregated properties of this point or cluster
+    pub fn properties(&self) -> &P {
+        &self.properties
+    }
+
+    /// Whether this point or cluster was removed since the index was built or compacted.
+    pub(crate) fn is_removed(&self) -> bool {
+        self.num_points == 0
+    }
 }
 
 /// Information describing a cluster of points.
 #[derive(Clone, Debug)]
-pub struct ClusterInfo {
+pub struct ClusterInfo<P = ()> {
     /// If this is a cluster,
     ///
-    /// If this is not a cluster, this references the positional index of data added to
-    /// Supercluster.
+    /// If this is not a cluster, this references the id of the input point.
     id: ClusterId,
 
-    /// The longitude of the cluster
+    /// The x coordinate of the cluster, usually longitude
     x: f64,
 
-    /// The latitude of the cluster
+    /// The y coordinate of the cluster, usually latitude
     y: f64,
 
     /// If true, references a cluster with containing data. Otherwise, is an original point that
@@ -115,57 +301,100 @@ pub struct ClusterInfo {
 
     /// Note: this will always be 1 if `is_cluster` is false
     point_count: usize,
+
+    /// The total weight of the points in the cluster
+    weight: f64,
+
+    /// The category shared by the points in the cluster
+    category: u32,
+
+    /// The bounding box of the points in the cluster, as `[min_x, min_y, max_x, max_y]`
+    bounds: [f64; 4],
+
+    /// The aggregated properties of the cluster, or the mapped properties of a single point.
+    properties: P,
 }
 
-impl From<ClusterInfo> for ClusterId {
-    fn from(value: ClusterInfo) -> Self {
+impl<P> From<ClusterInfo<P>> for ClusterId {
+    fn from(value: ClusterInfo<P>) -> Self {
         value.id()
     }
 }
 
-impl From<&ClusterInfo> for ClusterId {
-    fn from(value: &ClusterInfo) -> Self {
+impl<P> From<&ClusterInfo<P>> for ClusterId {
+    fn from(value: &ClusterInfo<P>) -> Self {
         value.id()
     }
 }
 
-impl ClusterInfo {
-    pub(crate) fn new_cluster(id: ClusterId, x: f64, y: f64, count: usize) -> Self {
+impl<P> ClusterInfo<P> {
+    /// NOTE: here the x and y must already be reprojected back to the user's own coordinate
+    /// system.
+    pub(crate) fn new_cluster(
+        id: ClusterId,
+        x: f64,
+        y: f64,
+        count: usize,
+        weight: f64,
+        category: u32,
+        properties: P,
+    ) -> Self {
         Self {
             id,
-            x: x_to_longitude(x),
-            y: y_to_latitude(y),
+            x,
+            y,
             cluster: true,
             point_count: count,
+            weight,
+            category,
+            bounds: [x, y, x, y],
+            properties,
         }
     }
 
     /// NOTE: here the x and y are already in the user's own coordinate system (usually lon-lat),
     /// so no need to reproject back.
-    pub(crate) fn new_leaf(id: ClusterId, x: f64, y: f64) -> Self {
+    pub(crate) fn new_leaf(
+        id: ClusterId,
+        x: f64,
+        y: f64,
+        weight: f64,
+        category: u32,
+        properties: P,
+    ) -> Self {
         Self {
             id,
             x,
             y,
             cluster: false,
             point_count: 1,
+            weight,
+            category,
+            bounds: [x, y, x, y],
+            properties,
         }
     }
 
+    /// Set the bounding box of the points in a cluster.
+    pub(crate) fn with_bounds(self, bounds: [f64; 4]) -> Self {
+        Self { bounds, ..self }
+    }
+
     /// If this is a cluster (i.e. [`cluster()`][Self::cluster] is `true`)
     ///
     /// If this is not a cluster (i.e. [`cluster()`][Self::cluster] is `false`), this references
-    /// the positional index of data originall added via SuperclusterBuilder.
+    /// the id of the input point, which is its position unless it was added with
+    /// [`SuperclusterBuilder::add_with_id`][crate::SuperclusterBuilder::add_with_id].
     pub fn id(&self) -> ClusterId {
         self.id
     }
 
-    /// The longitude of the cluster
+    /// The x coordinate of the cluster, in the units of the input points (usually longitude)
     pub fn x(&self) -> f64 {
         self.x
     }
 
-    /// The latitude of the cluster
+    /// The y coordinate of the cluster, in the units of the input points (usually latitude)
     pub fn y(&self) -> f64 {
         self.y
     }
@@ -184,6 +413,40 @@ impl ClusterInfo {
     pub fn count(&self) -> usize {
         self.point_count
     }
+
+    /// The total weight of the points in this cluster
+    ///
+    /// This equals [`count`][Self::count] unless points were added with
+    /// [`SuperclusterBuilder::add_weighted`][crate::SuperclusterBuilder::add_weighted].
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// The category of the points in this cluster
+    ///
+    /// Points are only clustered with points of the same category. This is `0` unless points
+    /// were added with
+    /// [`SuperclusterBuilder::add_with_category`][crate::SuperclusterBuilder::add_with_category].
+    pub fn category(&self) -> u32 {
+        self.category
+    }
+
+    /// The bounding box of the points in this cluster, as `[min_x, min_y, max_x, max_y]` in the
+    /// units of the input points.
+    ///
+    /// This can be used to fit a map view to a cluster without fetching its leaves. If this is
+    /// not a cluster, the bounding box is the point itself.
+    pub fn bounds(&self) -> [f64; 4] {
+        self.bounds
+    }
+
+    /// The properties of this cluster, as accumulated by a
+    /// [`ClusterAggregator`][crate::ClusterAggregator].
+    ///
+    /// If this is not a cluster, these are the mapped properties of the single input point.
+    pub fn properties(&self) -> &P {
+        &self.properties
+    }
 }
 
 #[cfg(test)]
@@ -191,9 +454,31 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_get_origin_idx() {
+    fn test_get_min_point_id() {
         let id = ClusterId::new_source_id(100);
-        let x = id.get_origin_idx(0);
-        dbg!(&x);
+        assert!(!id.is_cluster());
+        assert_eq!(id.point_id(), Some(100));
+        assert_eq!((id.min_point_id(), id.origin_zoom()), (None, None));
+
+        let id = ClusterId::new(100, 5);
+        assert!(id.is_cluster());
+        assert_eq!(id.point_id(), None);
+        assert_eq!((id.min_point_id(), id.origin_zoom()), (Some(100), Some(6)));
+
+        assert_eq!(
+            id.decode(),
+            ClusterIdKind::Cluster {
+                origin_zoom: 6,
+                min_point_id: 100
+            }
+        );
+        assert_eq!(
+            ClusterId::new_source_id(7).decode(),
+            ClusterIdKind::Leaf { point_id: 7 }
+        );
+
+        let id = ClusterId::new(ClusterId::MAX_POINT_ID, ClusterId::MAX_ORIGIN_ZOOM - 1);
+        assert_eq!(id.min_point_id(), Some(ClusterId::MAX_POINT_ID));
+        assert_eq!(id.origin_zoom(), Some(ClusterId::MAX_ORIGIN_ZOOM));
     }
 }
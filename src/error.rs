@@ -1,9 +1,60 @@
 use std::fmt::Debug;
 use thiserror::Error;
 
+use crate::cluster::ClusterId;
+use crate::options::SuperclusterOptions;
+
 /// Enum with all errors in this crate.
 #[derive(Error, Debug)]
 pub enum SuperclusterError {
     #[error("No cluster with the specified id.")]
     NoClusterFound,
+
+    #[error("No point with the specified id.")]
+    NoPointFound,
+
+    #[error("Invalid serialized index: {0}")]
+    InvalidBuffer(String),
+
+    #[error("The serialized index was built with a different projection.")]
+    ProjectionMismatch,
+
+    #[error("Minimum zoom {min_zoom} is greater than maximum zoom {max_zoom}.")]
+    InvalidZoomRange { min_zoom: usize, max_zoom: usize },
+
+    #[error(
+        "Maximum zoom {0} is greater than the largest supported zoom of {max}.",
+        max = SuperclusterOptions::MAX_ZOOM
+    )]
+    MaxZoomTooLarge(usize),
+
+    #[error("Radius must be positive and finite, got {0}.")]
+    InvalidRadius(f64),
+
+    #[error("Extent must be positive and finite, got {0}.")]
+    InvalidExtent(f64),
+
+    #[error("Node size must be between 2 and 65535, got {0}.")]
+    InvalidNodeSize(usize),
+
+    #[error("Projection bounds must be finite and non-empty, got {0:?}.")]
+    InvalidBounds([f64; 4]),
+
+    #[error("Point {index} has a non-finite or out of range coordinate ({x}, {y}).")]
+    InvalidCoordinate { index: usize, x: f64, y: f64 },
+
+    #[error("Point {index} has a weight of {weight}, but weights must be positive and finite.")]
+    InvalidWeight { index: usize, weight: f64 },
+
+    #[error(
+        "Point id {0} is greater than the largest supported id of {max}.",
+        max = ClusterId::MAX_POINT_ID
+    )]
+    InvalidPointId(u64),
+
+    #[error("Point id {0} was added more than once.")]
+    DuplicatePointId(u64),
+
+    #[error("Got {0} points, but at most {max} points are supported.", max = u32::MAX)]
+    TooManyPoints(usize),
 }
@@ -0,0 +1,872 @@
+//! The binary layout of a serialized [Supercluster] index.
+//!
+//! All values are stored in native byte order, and every section starts at a multiple of 8 bytes
+//! from the start of the buffer, padded with zeros. The layout is:
+//!
+//! - A header:
+//!   - `u32` magic number, which also detects a buffer written with a different byte order
+//!   - `u8` format version
+//!   - `u8` coordinate type, as the geo-index type index of `N`
+//!   - `u8` [CoordinatePolicy]: `0` to reject, `1` to skip and `2` to clamp
+//!   - 1 byte of padding
+//!   - `u64` [`min_zoom`][SuperclusterOptions::min_zoom],
+//!     [`max_zoom`][SuperclusterOptions::max_zoom] and
+//!     [`min_points`][SuperclusterOptions::min_points]
+//!   - `f64` [`radius`][SuperclusterOptions::radius] and
+//!     [`extent`][SuperclusterOptions::extent]
+//!   - `u64` [`node_size`][SuperclusterOptions::node_size]
+//!   - `u64` number of input points
+//!   - `u64` size in bytes of the properties of each cluster
+//!   - `u64` [tag][Projection::tag] of the projection, and `f64` min x, min y, max x and max y of
+//!     its [bounds][Projection::bounds]
+//! - The input points, as interleaved `f64` x and y values.
+//! - The `u64` id of each input point, in strictly ascending order.
+//! - For each zoom from `min_zoom` to `max_zoom + 1`:
+//!   - `u64` number of items, number of moved items, and byte length of the KD-tree buffer
+//!   - The geo-index KD-tree buffer, which may be empty if there are no items. Items that are
+//!     not in the tree were inserted since it was built.
+//!   - Columns of each item: `N` x, `N` y, `u64` id, `u64` parent id (`u64::MAX` if there is no
+//!     parent), `u64` number of points (`0` if the item was removed), `u64` index of the point
+//!     with the smallest id, `f64` weight, `f64` min x, min y, max x and max y of the points,
+//!     `u32` category and properties.
+//!   - Columns of each moved item: `u64` index, `N` displayed x and `N` displayed y.
+//! - `u64` number of clusters, and the `u64` [ClusterId] of each cluster followed by the id it is
+//!   stored with in the zoom levels, in ascending order of [ClusterId].
+//! - `u64` number of skipped points, and their `u64` ids in ascending order.
+//! - `u64` number of clamped points, and their `u64` ids in ascending order.
+
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::sync::Arc;
+
+use bytemuck::Pod;
+
+use crate::builder::CoordinatePolicy;
+use crate::cluster::{ClusterData, ClusterId};
+use crate::error::SuperclusterError;
+use crate::index::ClusterIndex;
+use crate::options::SuperclusterOptions;
+use crate::projection::{Projection, WebMercator};
+use crate::r#type::ClusterFloat;
+use crate::tree::{TreeBuffer, TreeWithData};
+use crate::Supercluster;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"SPCL");
+
+/// The version of the binary layout, incremented on incompatible changes.
+const VERSION: u8 = 1;
+
+/// The size of the header, in bytes.
+const HEADER_SIZE: usize = 112;
+
+/// The parent id of items without a parent, which is never a valid [ClusterId].
+const NO_PARENT: u64 = u64::MAX;
+
+/// Magic number of geo-index KD-tree buffers.
+const KDBUSH_MAGIC: u8 = 0xdb;
+
+/// Version of geo-index KD-tree buffers.
+const KDBUSH_VERSION: u8 = 1;
+
+/// The header of a serialized index.
+pub(crate) struct Header {
+    pub(crate) options: SuperclusterOptions,
+    pub(crate) coordinate_policy: CoordinatePolicy,
+    pub(crate) num_points: usize,
+}
+
+/// The sections of a serialized zoom level.
+pub(crate) struct LevelBytes<'a> {
+    pub(crate) num_items: usize,
+    pub(crate) num_moved: usize,
+    /// The number of items in the KD-tree
+    pub(crate) num_indexed: usize,
+    pub(crate) tree: &'a [u8],
+    pub(crate) x: &'a [u8],
+    pub(crate) y: &'a [u8],
+    pub(crate) source_id: &'a [u8],
+    pub(crate) parent_id: &'a [u8],
+    pub(crate) num_points: &'a [u8],
+    pub(crate) min_point: &'a [u8],
+    pub(crate) weight: &'a [u8],
+    pub(crate) bounds: &'a [u8],
+    pub(crate) category: &'a [u8],
+    pub(crate) properties: &'a [u8],
+    pub(crate) moved_idx: &'a [u8],
+    pub(crate) moved_x: &'a [u8],
+    pub(crate) moved_y: &'a [u8],
+}
+
+/// Reads sections from a serialized index.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Read a section of `len` bytes, skipping the padding after it.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SuperclusterError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of buffer"))?;
+        let section = &self.bytes[self.pos..end];
+        self.pos = usize::min(end.next_multiple_of(8), self.bytes.len());
+        Ok(section)
+    }
+
+    /// Read a section of `len` values of `T`.
+    fn column<T>(&mut self, len: usize) -> Result<&'a [u8], SuperclusterError> {
+        let byte_len = len
+            .checked_mul(size_of::<T>())
+            .ok_or_else(|| invalid("column is too long"))?;
+        self.take(byte_len)
+    }
+
+    fn u64(&mut self) -> Result<u64, SuperclusterError> {
+        Ok(u64::from_ne_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, SuperclusterError> {
+        self.u64()?
+            .try_into()
+            .map_err(|_| invalid("value does not fit in usize"))
+    }
+
+    fn f64(&mut self) -> Result<f64, SuperclusterError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    /// Read the header, checking that it matches the coordinate and property types and the
+    /// projection.
+    pub(crate) fn header<N: ClusterFloat, P>(
+        &mut self,
+        projection: &dyn Projection,
+    ) -> Result<Header, SuperclusterError> {
+        let start = self.take(8)?;
+        if u32::from_ne_bytes(start[0..4].try_into().unwrap()) != MAGIC {
+            return Err(invalid(
+                "not a serialized index, or written with a different byte order",
+            ));
+        }
+        if start[4] != VERSION {
+            return Err(invalid(format!(
+                "got version {} when expected version {}",
+                start[4], VERSION
+            )));
+        }
+        if start[5] != N::TYPE_INDEX {
+            return Err(invalid(format!(
+                "got coordinate type {} when expected type {}",
+                start[5],
+                N::TYPE_INDEX
+            )));
+        }
+        let coordinate_policy = match start[6] {
+            0 => CoordinatePolicy::Reject,
+            1 => CoordinatePolicy::Skip,
+            2 => CoordinatePolicy::Clamp,
+            policy => return Err(invalid(format!("unknown coordinate policy {policy}"))),
+        };
+
+        let options = SuperclusterOptions {
+            min_zoom: self.usize()?,
+            max_zoom: self.usize()?,
+            min_points: self.usize()?,
+            radius: self.f64()?,
+            extent: self.f64()?,
+            node_size: self.usize()?,
+        };
+        options.validate().map_err(|err| invalid(err.to_string()))?;
+
+        let num_points = self.usize()?;
+
+        let property_size = self.usize()?;
+        if property_size != size_of::<P>() {
+            return Err(invalid(format!(
+                "got properties of {} bytes when expected {} bytes",
+                property_size,
+                size_of::<P>()
+            )));
+        }
+
+        let tag = self.u64()?;
+        let bounds = [self.f64()?, self.f64()?, self.f64()?, self.f64()?];
+        if tag != projection.tag() || bounds != projection.bounds() {
+            return Err(SuperclusterError::ProjectionMismatch);
+        }
+
+        Ok(Header {
+            options,
+            coordinate_policy,
+            num_points,
+        })
+    }
+
+    /// Read the interleaved x and y values of `num_points` input points.
+    pub(crate) fn points(&mut self, num_points: usize) -> Result<&'a [u8], SuperclusterError> {
+        self.column::<[f64; 2]>(num_points)
+    }
+
+    /// Read the ids of `num_points` input points.
+    pub(crate) fn ids(&mut self, num_points: usize) -> Result<&'a [u8], SuperclusterError> {
+        self.column::<u64>(num_points)
+    }
+
+    /// Read a length-prefixed list of point ids, such as the skipped points.
+    pub(crate) fn id_list(&mut self) -> Result<&'a [u8], SuperclusterError> {
+        let len = self.usize()?;
+        self.column::<u64>(len)
+    }
+
+    /// Read the public and stored ids of the clusters.
+    pub(crate) fn cluster_ids(&mut self) -> Result<&'a [u8], SuperclusterError> {
+        let len = self.usize()?;
+        self.column::<[u64; 2]>(len)
+    }
+
+    /// Read the sections of a zoom level.
+    pub(crate) fn level<N: ClusterFloat, P>(
+        &mut self,
+    ) -> Result<LevelBytes<'a>, SuperclusterError> {
+        let num_items = self.usize()?;
+        let num_moved = self.usize()?;
+        let tree_len = self.usize()?;
+
+        let tree = self.take(tree_len)?;
+        let num_indexed = tree_num_items::<N>(tree)?;
+        if num_indexed > num_items {
+            return Err(invalid("KD-tree has more items than the zoom level"));
+        }
+
+        Ok(LevelBytes {
+            num_items,
+            num_moved,
+            num_indexed,
+            tree,
+            x: self.column::<N>(num_items)?,
+            y: self.column::<N>(num_items)?,
+            source_id: self.column::<u64>(num_items)?,
+            parent_id: self.column::<u64>(num_items)?,
+            num_points: self.column::<u64>(num_items)?,
+            min_point: self.column::<u64>(num_items)?,
+            weight: self.column::<f64>(num_items)?,
+            bounds: self.column::<[f64; 4]>(num_items)?,
+            category: self.column::<u32>(num_items)?,
+            properties: self.column::<P>(num_items)?,
+            moved_idx: self.column::<u64>(num_moved)?,
+            moved_x: self.column::<N>(num_moved)?,
+            moved_y: self.column::<N>(num_moved)?,
+        })
+    }
+}
+
+/// Validate a geo-index KD-tree buffer of `N`, returning its number of items.
+fn tree_num_items<N: ClusterFloat>(tree: &[u8]) -> Result<usize, SuperclusterError> {
+    if tree.is_empty() {
+        return Ok(0);
+    }
+    if tree.len() < 8 || tree[0] != KDBUSH_MAGIC {
+        return Err(invalid("KD-tree is not in kdbush format"));
+    }
+    if tree[1] != (KDBUSH_VERSION << 4) + N::TYPE_INDEX {
+        return Err(invalid(
+            "KD-tree has an unexpected version or coordinate type",
+        ));
+    }
+
+    let num_items = u32::from_ne_bytes(tree[4..8].try_into().unwrap()) as usize;
+    let id_size = if num_items < 65536 { 2 } else { 4 };
+    let ids_byte_size = num_items * id_size;
+    let coords_byte_size = num_items * 2 * size_of::<N>();
+    let expected_len = 8 + ids_byte_size.next_multiple_of(8) + coords_byte_size;
+    if tree.len() != expected_len || num_items == 0 {
+        return Err(invalid("KD-tree has an unexpected length"));
+    }
+
+    Ok(num_items)
+}
+
+/// Check that the ids of a KD-tree buffer with `num_items` items, validated by
+/// [tree_num_items], refer to the items it was built with.
+fn check_tree_ids(tree: &[u8], num_items: usize) -> Result<(), SuperclusterError> {
+    if num_items == 0 {
+        return Ok(());
+    }
+    let id_size = if num_items < 65536 { 2 } else { 4 };
+    let mut ids = tree[8..8 + num_items * id_size]
+        .chunks_exact(id_size)
+        .map(|id| {
+            if id_size == 2 {
+                u16::from_ne_bytes(id.try_into().unwrap()) as usize
+            } else {
+                u32::from_ne_bytes(id.try_into().unwrap()) as usize
+            }
+        });
+    if ids.any(|id| id >= num_items) {
+        return Err(invalid("KD-tree item is out of bounds"));
+    }
+    Ok(())
+}
+
+/// Check that the ids of the items and KD-trees of the zoom levels from `min_zoom` to
+/// `max_zoom + 1` refer to existing input points and items, so that queries on a corrupted buffer
+/// can't index out of bounds.
+pub(crate) fn check_levels(
+    levels: &[LevelBytes],
+    min_zoom: usize,
+    num_points: usize,
+) -> Result<(), SuperclusterError> {
+    let level_len = |zoom: usize| {
+        let level = levels.get(zoom.checked_sub(min_zoom)?)?;
+        Some(level.num_items)
+    };
+
+    for (i, level) in levels.iter().enumerate() {
+        check_tree_ids(level.tree, level.num_indexed)?;
+
+        let zoom = min_zoom + i;
+        let full_resolution = i == levels.len() - 1;
+        let source_ids = read_column::<u64>(level.source_id, level.num_items);
+        let parent_ids = read_column::<u64>(level.parent_id, level.num_items);
+        let counts = read_column::<u64>(level.num_points, level.num_items);
+        let min_points = read_column::<u64>(level.min_point, level.num_items);
+
+        for (idx, (((source_id, parent_id), count), min_point)) in source_ids
+            .iter()
+            .zip(&parent_ids)
+            .zip(&counts)
+            .zip(&min_points)
+            .enumerate()
+        {
+            if *min_point >= num_points as u64 {
+                return Err(invalid("point index is out of bounds"));
+            }
+
+            let source_id = ClusterId::from(*source_id);
+            if source_id.is_cluster() {
+                // Clusters are grown from an item of a higher zoom, and have several points
+                let origin_zoom = source_id.get_origin_zoom();
+                if *count == 1
+                    || origin_zoom <= zoom
+                    || level_len(origin_zoom).is_none_or(|len| source_id.get_origin_idx() >= len)
+                {
+                    return Err(invalid("cluster id is out of bounds"));
+                }
+            } else if *count > 1
+                || source_id.as_u64() >= num_points as u64
+                || (full_resolution && source_id.as_u64() != idx as u64)
+            {
+                return Err(invalid("point index is out of bounds"));
+            }
+
+            if *parent_id != NO_PARENT {
+                // Parents are grown from an item of this zoom, at the next lower zoom
+                let parent_id = ClusterId::from(*parent_id);
+                if !parent_id.is_cluster()
+                    || zoom == min_zoom
+                    || parent_id.get_origin_zoom() != zoom
+                    || parent_id.get_origin_idx() >= level.num_items
+                {
+                    return Err(invalid("parent id is out of bounds"));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the public ids of clusters are strictly ascending, so that they can be searched,
+/// and that the ids they are stored with refer to existing items of the zoom levels from
+/// `min_zoom` to `max_zoom + 1`.
+pub(crate) fn check_cluster_ids(
+    cluster_ids: &[[u64; 2]],
+    levels: &[LevelBytes],
+    min_zoom: usize,
+) -> Result<(), SuperclusterError> {
+    if cluster_ids.windows(2).any(|pair| pair[0][0] >= pair[1][0]) {
+        return Err(invalid("cluster ids are not strictly ascending"));
+    }
+
+    for [public_id, stored_id] in cluster_ids {
+        let (public_id, stored_id) = (ClusterId::from(*public_id), ClusterId::from(*stored_id));
+        let origin_zoom = stored_id.get_origin_zoom();
+        let level = origin_zoom
+            .checked_sub(min_zoom)
+            .and_then(|i| levels.get(i));
+        if !public_id.is_cluster()
+            || !stored_id.is_cluster()
+            || public_id.get_origin_zoom() != origin_zoom
+            || origin_zoom <= min_zoom
+            || level.is_none_or(|level| stored_id.get_origin_idx() >= level.num_items)
+        {
+            return Err(invalid("cluster id is out of bounds"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a column of possibly unaligned values.
+pub(crate) fn read_column<T: Pod>(bytes: &[u8], len: usize) -> Vec<T> {
+    if size_of::<T>() == 0 {
+        return vec![T::zeroed(); len];
+    }
+    bytes
+        .chunks_exact(size_of::<T>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+/// Check that the ids of the input points are strictly ascending, so that they can be searched.
+pub(crate) fn check_ids(ids: &[u64]) -> Result<(), SuperclusterError> {
+    if ids.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(invalid("point ids are not strictly ascending"));
+    }
+    Ok(())
+}
+
+fn invalid(message: impl Into<String>) -> SuperclusterError {
+    SuperclusterError::InvalidBuffer(message.into())
+}
+
+/// Writes sections of a serialized index.
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    /// Write a section, padding it to a multiple of 8 bytes.
+    fn column<T: Pod>(&mut self, values: &[T]) {
+        if size_of::<T>() > 0 {
+            self.bytes.extend_from_slice(bytemuck::cast_slice(values));
+        }
+        self.bytes.resize(self.bytes.len().next_multiple_of(8), 0);
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.column(&[value]);
+    }
+}
+
+impl<N: ClusterFloat, P: Pod> Supercluster<N, P> {
+    /// Serialize this index to bytes, so that it can be loaded with
+    /// [`from_bytes`][Self::from_bytes] without clustering the points again.
+    ///
+    /// The [tag][Projection::tag] and [bounds][Projection::bounds] of the projection are stored,
+    /// but not the projection itself, so it must be passed again to
+    /// [`from_bytes_with_projection`][Self::from_bytes_with_projection] or
+    /// [`SuperclusterRef::try_new_with_projection`][crate::SuperclusterRef::try_new_with_projection]
+    /// unless it is the default [`WebMercator`]. Loading with a different projection returns
+    /// [`SuperclusterError::ProjectionMismatch`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer {
+            bytes: Vec::with_capacity(HEADER_SIZE),
+        };
+
+        let mut start = [0; 8];
+        start[0..4].copy_from_slice(&MAGIC.to_ne_bytes());
+        start[4] = VERSION;
+        start[5] = N::TYPE_INDEX;
+        start[6] = match self.coordinate_policy {
+            CoordinatePolicy::Reject => 0,
+            CoordinatePolicy::Skip => 1,
+            CoordinatePolicy::Clamp => 2,
+        };
+        writer.column(&start);
+
+        let options = &self.options;
+        writer.u64(options.min_zoom as u64);
+        writer.u64(options.max_zoom as u64);
+        writer.u64(options.min_points as u64);
+        writer.u64(options.radius.to_bits());
+        writer.u64(options.extent.to_bits());
+        writer.u64(options.node_size as u64);
+        writer.u64(self.points.len() as u64);
+        writer.u64(size_of::<P>() as u64);
+        writer.u64(self.projection.tag());
+        for bound in self.projection.bounds() {
+            writer.u64(bound.to_bits());
+        }
+        debug_assert_eq!(writer.bytes.len(), HEADER_SIZE);
+
+        let points: Vec<[f64; 2]> = self.points.iter().map(|(x, y)| [*x, *y]).collect();
+        writer.column(&points);
+        writer.column(&self.ids);
+
+        for zoom in options.min_zoom..=options.max_zoom + 1 {
+            let tree_with_data = &self.trees[&zoom];
+            let data = tree_with_data.data();
+            let tree = tree_with_data
+                .tree
+                .as_ref()
+                .map_or(&[][..], |tree| tree.as_bytes());
+            let moved = &tree_with_data.moved;
+
+            writer.u64(data.len() as u64);
+            writer.u64(moved.len() as u64);
+            writer.u64(tree.len() as u64);
+            writer.column(tree);
+
+            let x: Vec<N> = data.iter().map(|item| item.x).collect();
+            let y: Vec<N> = data.iter().map(|item| item.y).collect();
+            let source_id: Vec<u64> = data.iter().map(|item| item.source_id.as_u64()).collect();
+            let parent_id: Vec<u64> = data
+                .iter()
+                .map(|item| {
+                    item.parent_id
+                        .map_or(NO_PARENT, |parent_id| parent_id.as_u64())
+                })
+                .collect();
+            let num_points: Vec<u64> = data.iter().map(|item| item.num_points as u64).collect();
+            let min_point: Vec<u64> = data.iter().map(|item| item.min_point as u64).collect();
+            let weight: Vec<f64> = data.iter().map(|item| item.weight).collect();
+            let bounds: Vec<[f64; 4]> = data.iter().map(|item| item.bounds).collect();
+            let category: Vec<u32> = data.iter().map(|item| item.category).collect();
+            let properties: Vec<P> = data.iter().map(|item| item.properties).collect();
+            writer.column(&x);
+            writer.column(&y);
+            writer.column(&source_id);
+            writer.column(&parent_id);
+            writer.column(&num_points);
+            writer.column(&min_point);
+            writer.column(&weight);
+            writer.column(&bounds);
+            writer.column(&category);
+            writer.column(&properties);
+
+            let moved_idx: Vec<u64> = moved.keys().map(|i| *i as u64).collect();
+            let moved_x: Vec<N> = moved.values().map(|(x, _)| *x).collect();
+            let moved_y: Vec<N> = moved.values().map(|(_, y)| *y).collect();
+            writer.column(&moved_idx);
+            writer.column(&moved_x);
+            writer.column(&moved_y);
+        }
+
+        let cluster_ids: Vec<[u64; 2]> = self
+            .cluster_ids()
+            .into_iter()
+            .map(|(public_id, stored_id)| [public_id.as_u64(), stored_id.as_u64()])
+            .collect();
+        writer.u64(cluster_ids.len() as u64);
+        writer.column(&cluster_ids);
+
+        writer.u64(self.skipped_points.len() as u64);
+        writer.column(&self.skipped_points);
+        writer.u64(self.clamped_points.len() as u64);
+        writer.column(&self.clamped_points);
+
+        writer.bytes
+    }
+
+    /// Load an index serialized with [`to_bytes`][Self::to_bytes], whose points are projected
+    /// with the default [`WebMercator`] projection.
+    ///
+    /// The coordinate type `N` and property type `P` must match those of the serialized index.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SuperclusterError> {
+        Self::from_bytes_with_projection(bytes, WebMercator::default())
+    }
+
+    /// Load an index serialized with [`to_bytes`][Self::to_bytes], whose points are projected
+    /// with the given projection.
+    ///
+    /// The projection must be the one the index was built with, otherwise this returns
+    /// [`SuperclusterError::ProjectionMismatch`].
+    pub fn from_bytes_with_projection(
+        bytes: &[u8],
+        projection: impl Projection + 'static,
+    ) -> Result<Self, SuperclusterError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<N, P>(&projection)?;
+        let options = header.options;
+
+        let points = read_column::<[f64; 2]>(reader.points(header.num_points)?, header.num_points)
+            .into_iter()
+            .map(|[x, y]| (x, y))
+            .collect();
+        let ids = read_column::<u64>(reader.ids(header.num_points)?, header.num_points);
+        check_ids(&ids)?;
+
+        let levels = (options.min_zoom..=options.max_zoom + 1)
+            .map(|_| reader.level::<N, P>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if levels.last().unwrap().num_items != header.num_points {
+            return Err(invalid(
+                "full resolution zoom does not match the input points",
+            ));
+        }
+        check_levels(&levels, options.min_zoom, header.num_points)?;
+
+        let cluster_ids = reader.cluster_ids()?;
+        let cluster_ids = read_column::<[u64; 2]>(cluster_ids, cluster_ids.len() / 16);
+        check_cluster_ids(&cluster_ids, &levels, options.min_zoom)?;
+
+        let mut trees = HashMap::with_capacity(levels.len());
+        for (zoom, level) in (options.min_zoom..).zip(&levels) {
+            trees.insert(zoom, level_to_tree(level)?);
+        }
+
+        let skipped_points = reader.id_list()?;
+        let skipped_points = read_column::<u64>(skipped_points, skipped_points.len() / 8);
+        check_ids(&skipped_points)?;
+        let clamped_points = reader.id_list()?;
+        let clamped_points = read_column::<u64>(clamped_points, clamped_points.len() / 8);
+        check_ids(&clamped_points)?;
+
+        let mut supercluster = Supercluster::new(points, ids, trees, options, Arc::new(projection));
+        supercluster.coordinate_policy = header.coordinate_policy;
+        supercluster.skipped_points = skipped_points;
+        supercluster.clamped_points = clamped_points;
+        supercluster.stored_ids = cluster_ids
+            .into_iter()
+            .map(|[public_id, stored_id]| (ClusterId::from(public_id), ClusterId::from(stored_id)))
+            .collect();
+        Ok(supercluster)
+    }
+}
+
+/// Copy a serialized zoom level.
+fn level_to_tree<N: ClusterFloat, P: Pod>(
+    level: &LevelBytes,
+) -> Result<TreeWithData<N, P>, SuperclusterError> {
+    let n = level.num_items;
+    let x = read_column::<N>(level.x, n);
+    let y = read_column::<N>(level.y, n);
+    let source_id = read_column::<u64>(level.source_id, n);
+    let parent_id = read_column::<u64>(level.parent_id, n);
+    let num_points = read_column::<u64>(level.num_points, n);
+    let min_point = read_column::<u64>(level.min_point, n);
+    let weight = read_column::<f64>(level.weight, n);
+    let bounds = read_column::<[f64; 4]>(level.bounds, n);
+    let category = read_column::<u32>(level.category, n);
+    let properties = read_column::<P>(level.properties, n);
+
+    let mut data = Vec::with_capacity(n);
+    for i in 0..n {
+        data.push(ClusterData {
+            x: x[i],
+            y: y[i],
+            zoom: None,
+            source_id: ClusterId::from(source_id[i]),
+            parent_id: (parent_id[i] != NO_PARENT).then(|| ClusterId::from(parent_id[i])),
+            num_points: num_points[i] as usize,
+            min_point: min_point[i] as usize,
+            weight: weight[i],
+            bounds: bounds[i],
+            category: category[i],
+            properties: properties[i],
+        });
+    }
+
+    let moved_idx = read_column::<u64>(level.moved_idx, level.num_moved);
+    let moved_x = read_column::<N>(level.moved_x, level.num_moved);
+    let moved_y = read_column::<N>(level.moved_y, level.num_moved);
+    let mut moved = BTreeMap::new();
+    for i in 0..level.num_moved {
+        let idx = moved_idx[i] as usize;
+        if idx >= n {
+            return Err(invalid("moved item is out of bounds"));
+        }
+        moved.insert(idx, (moved_x[i], moved_y[i]));
+    }
+
+    Ok(TreeWithData {
+        tree: (!level.tree.is_empty()).then(|| TreeBuffer::new(level.tree)),
+        data,
+        pending: (level.num_indexed..n).collect(),
+        moved,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SuperclusterError;
+    use crate::test::index::{build_index, PointSum};
+    use crate::test::load_fixture::load_places;
+    use crate::{
+        ClusterData, ClusterId, CoordinatePolicy, MercatorUnits, Planar, PolarStereographic,
+        Supercluster, SuperclusterBuilder, SuperclusterOptions,
+    };
+
+    fn assert_same_clusters(a: &Supercluster<f32, u32>, b: &Supercluster<f32, u32>) {
+        for zoom in 0..=17 {
+            let a_clusters = a.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let b_clusters = b.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            assert_eq!(a_clusters.len(), b_clusters.len());
+            for (a_cluster, b_cluster) in a_clusters.iter().zip(b_clusters.iter()) {
+                assert_eq!(a_cluster.id(), b_cluster.id());
+                assert_eq!(a_cluster.count(), b_cluster.count());
+                assert_eq!(a_cluster.properties(), b_cluster.properties());
+                assert_eq!(
+                    (a_cluster.x(), a_cluster.y()),
+                    (b_cluster.x(), b_cluster.y())
+                );
+                assert_eq!(a_cluster.bounds(), b_cluster.bounds());
+            }
+            assert_eq!(a.get_tile(zoom, 0, 0), b.get_tile(zoom, 0, 0));
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::<f32>::new_with_coord_type(
+            coords.len(),
+            SuperclusterOptions::default(),
+        );
+        for coord in &coords {
+            builder.add(coord[0], coord[1]);
+        }
+        let mut supercluster = builder.finish_with_aggregator(&PointSum);
+
+        let loaded = Supercluster::<f32, u32>::from_bytes(&supercluster.to_bytes()).unwrap();
+        assert_same_clusters(&supercluster, &loaded);
+
+        // Pending and moved items of incremental updates are kept
+        for id in (0..coords.len() as u64).step_by(5) {
+            supercluster.remove_with_aggregator(id, &PointSum).unwrap();
+        }
+        for coord in coords.iter().step_by(5) {
+            supercluster
+                .insert_with_aggregator(coord[0] + 0.1, coord[1], &PointSum)
+                .unwrap();
+        }
+        let mut loaded = Supercluster::<f32, u32>::from_bytes(&supercluster.to_bytes()).unwrap();
+        assert_same_clusters(&supercluster, &loaded);
+
+        supercluster.remove_with_aggregator(1, &PointSum).unwrap();
+        loaded.remove_with_aggregator(1, &PointSum).unwrap();
+        assert_same_clusters(&supercluster, &loaded);
+    }
+
+    #[test]
+    fn test_roundtrip_projection() {
+        let coords: Vec<_> = load_places()
+            .into_iter()
+            .filter(|coord| coord[1] > 0.0)
+            .collect();
+        let mut builder =
+            SuperclusterBuilder::new(coords.len()).with_projection(PolarStereographic::north());
+        for coord in &coords {
+            builder.add(coord[0], coord[1]);
+        }
+        let supercluster = builder.finish();
+
+        let loaded = Supercluster::<f64>::from_bytes_with_projection(
+            &supercluster.to_bytes(),
+            PolarStereographic::north(),
+        )
+        .unwrap();
+        let clusters = supercluster.get_clusters(0.0, 40.0, 90.0, 90.0, 3);
+        let loaded_clusters = loaded.get_clusters(0.0, 40.0, 90.0, 90.0, 3);
+        assert_eq!(clusters.len(), loaded_clusters.len());
+
+        // A different projection, or the same projection with other parameters, is rejected
+        let bytes = supercluster.to_bytes();
+        assert!(matches!(
+            Supercluster::<f64>::from_bytes(&bytes),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+        assert!(matches!(
+            Supercluster::<f64>::from_bytes_with_projection(&bytes, PolarStereographic::south()),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+
+        let mut builder = SuperclusterBuilder::new(1).with_planar_extent(0.0, 0.0, 10.0, 10.0);
+        builder.add(5.0, 5.0);
+        let bytes = builder.finish().to_bytes();
+        assert!(Supercluster::<f64>::from_bytes_with_projection(
+            &bytes,
+            Planar::new(0.0, 0.0, 10.0, 10.0)
+        )
+        .is_ok());
+        assert!(matches!(
+            Supercluster::<f64>::from_bytes_with_projection(
+                &bytes,
+                Planar::new(0.0, 0.0, 20.0, 20.0)
+            ),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+
+        let mut builder = SuperclusterBuilder::new(1).with_units(MercatorUnits::Meters);
+        builder.add(0.0, 0.0);
+        let bytes = builder.finish().to_bytes();
+        assert!(matches!(
+            Supercluster::<f64>::from_bytes(&bytes),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip_coordinate_policy() {
+        let mut builder =
+            SuperclusterBuilder::new(4).with_coordinate_policy(CoordinatePolicy::Skip);
+        builder.add(10.0, 10.0);
+        builder.add(f64::NAN, 10.0);
+        builder.add(10.0, 95.0);
+        builder.add(20.0, 20.0);
+        let supercluster = builder.finish();
+
+        let mut loaded = Supercluster::<f64>::from_bytes(&supercluster.to_bytes()).unwrap();
+        assert_eq!(loaded.skipped_points(), &[1, 2]);
+        assert!(loaded.clamped_points().is_empty());
+
+        // The coordinate policy still applies to inserted points
+        let id = loaded.insert(f64::NAN, 0.0).unwrap();
+        assert_eq!(loaded.skipped_points(), &[1, 2, id]);
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let coords = load_places();
+        let bytes = build_index(&coords).to_bytes();
+
+        assert!(Supercluster::<f64>::from_bytes(&bytes).is_ok());
+        assert!(Supercluster::<f64>::from_bytes(&bytes[..bytes.len() - 8]).is_err());
+        assert!(Supercluster::<f64>::from_bytes(&bytes[1..]).is_err());
+        assert!(Supercluster::<f32>::from_bytes(&bytes).is_err());
+        assert!(Supercluster::<f64, u64>::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+        let max_zoom = supercluster.options.max_zoom;
+
+        let corruptions: [fn(&mut ClusterData<f64, ()>); 4] = [
+            |item| item.source_id = ClusterId::new_source_id(1 << 40),
+            |item| item.source_id = ClusterId::new(1 << 40, 5),
+            |item| item.parent_id = Some(ClusterId::new(0, 0)),
+            |item| item.parent_id = Some(ClusterId::new(1 << 40, 3)),
+        ];
+        for corrupt in corruptions {
+            let mut corrupted = build_index(&coords);
+            let level = corrupted.trees.get_mut(&(max_zoom - 1)).unwrap();
+            corrupt(&mut level.data[0]);
+            assert!(matches!(
+                Supercluster::<f64>::from_bytes(&corrupted.to_bytes()),
+                Err(SuperclusterError::InvalidBuffer(_))
+            ));
+        }
+
+        // An id of the KD-tree of zoom 0 out of bounds
+        let mut bytes = supercluster.to_bytes();
+        let tree = supercluster.trees[&0].tree.as_ref().unwrap().as_bytes();
+        let offset = bytes
+            .windows(tree.len())
+            .position(|window| window == tree)
+            .unwrap();
+        bytes[offset + 8..offset + 10].copy_from_slice(&u16::MAX.to_ne_bytes());
+        assert!(matches!(
+            Supercluster::<f64>::from_bytes(&bytes),
+            Err(SuperclusterError::InvalidBuffer(_))
+        ));
+    }
+}
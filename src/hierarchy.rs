@@ -0,0 +1,91 @@
+use crate::cluster::ClusterId;
+
+/// A point or cluster at a single zoom of the cluster hierarchy, as returned by
+/// [`Supercluster::hierarchy`][crate::Supercluster::hierarchy].
+///
+/// Together, the entries of all zooms form an edge table of the hierarchy: each entry refers to
+/// the cluster it is merged into at the next lower zoom by its
+/// [`parent_id`][Self::parent_id].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HierarchyEntry {
+    /// The zoom of this entry
+    zoom: usize,
+
+    /// The cluster id if this is a cluster, otherwise the id of the input point.
+    id: ClusterId,
+
+    /// The id of the cluster this is merged into at `zoom - 1`, if any
+    parent_id: Option<ClusterId>,
+
+    /// The x coordinate, usually longitude
+    x: f64,
+
+    /// The y coordinate, usually latitude
+    y: f64,
+
+    /// Note: this will always be 1 if this is not a cluster
+    point_count: usize,
+}
+
+impl HierarchyEntry {
+    pub(crate) fn new(
+        zoom: usize,
+        id: ClusterId,
+        parent_id: Option<ClusterId>,
+        x: f64,
+        y: f64,
+        point_count: usize,
+    ) -> Self {
+        Self {
+            zoom,
+            id,
+            parent_id,
+            x,
+            y,
+            point_count,
+        }
+    }
+
+    /// The zoom of this entry.
+    ///
+    /// The input points are at `max_zoom + 1`, and clusters formed at zoom `z` first appear at
+    /// zoom `z`.
+    pub fn zoom(&self) -> usize {
+        self.zoom
+    }
+
+    /// The id of this cluster, or of the input point if this is not a cluster.
+    pub fn id(&self) -> ClusterId {
+        self.id
+    }
+
+    /// The id of the cluster this point or cluster is merged into at `zoom - 1`.
+    ///
+    /// If this is `None`, the point or cluster is carried over to `zoom - 1` unchanged, and has
+    /// an entry with the same id there. It is also `None` at `min_zoom`.
+    pub fn parent_id(&self) -> Option<ClusterId> {
+        self.parent_id
+    }
+
+    /// The x coordinate of the cluster, in the units of the input points (usually longitude)
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    /// The y coordinate of the cluster, in the units of the input points (usually latitude)
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    /// Whether this entry represents a cluster or a single input point.
+    pub fn is_cluster(&self) -> bool {
+        self.id.is_cluster()
+    }
+
+    /// The number of points contained in this cluster
+    ///
+    /// This will always be 1 if [`is_cluster`][Self::is_cluster] is `false`.
+    pub fn count(&self) -> usize {
+        self.point_count
+    }
+}
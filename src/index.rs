@@ -0,0 +1,726 @@
+use std::collections::BTreeMap;
+
+use crate::cluster::{ClusterId, ClusterInfo};
+use crate::error::SuperclusterError;
+use crate::hierarchy::HierarchyEntry;
+use crate::options::SuperclusterOptions;
+use crate::polygon::PolygonFilter;
+use crate::projection::Projection;
+use crate::r#type::ClusterFloat;
+use crate::tile::TileFeature;
+use crate::util::convex_hull;
+
+/// The points and clusters of an index at a single zoom.
+///
+/// This is implemented by both owned and borrowed indexes, so that they can share query logic.
+pub(crate) trait ClusterLevel<N: ClusterFloat, P> {
+    /// The indexed position of the item at index `i`, used to find its children and parent.
+    fn anchor(&self, i: usize) -> (N, N);
+
+    /// The displayed position of the item at index `i`.
+    fn position(&self, i: usize) -> (N, N);
+
+    fn source_id(&self, i: usize) -> ClusterId;
+
+    fn parent_id(&self, i: usize) -> Option<ClusterId>;
+
+    fn num_points(&self, i: usize) -> usize;
+
+    /// The position of the input point with the smallest id among the points of the item at
+    /// index `i`.
+    fn min_point(&self, i: usize) -> usize;
+
+    fn weight(&self, i: usize) -> f64;
+
+    fn category(&self, i: usize) -> u32;
+
+    /// The bounding box of the points of the item at index `i`, in the user's original
+    /// coordinate system.
+    fn bounds(&self, i: usize) -> [f64; 4];
+
+    fn properties(&self, i: usize) -> P;
+
+    /// The number of items, including removed items.
+    fn len(&self) -> usize;
+
+    /// Find the indices of all items that have not been removed whose indexed position is within
+    /// `r` of `(qx, qy)`.
+    fn within(&self, qx: N, qy: N, r: N) -> Vec<usize>;
+
+    /// Find the indices of all items that have not been removed whose displayed position is
+    /// within `r` of `(qx, qy)`.
+    fn displayed_within(&self, qx: N, qy: N, r: N) -> Vec<usize>;
+
+    /// Find the indices of all items that have not been removed whose displayed position is within
+    /// the given bounding box.
+    fn range(&self, min_x: N, min_y: N, max_x: N, max_y: N) -> Vec<usize>;
+}
+
+/// A hierarchical cluster index, with one [ClusterLevel] per zoom.
+///
+/// The provided methods implement the queries of [Supercluster][crate::Supercluster].
+pub(crate) trait ClusterIndex<N: ClusterFloat, P> {
+    type Level: ClusterLevel<N, P>;
+
+    fn options(&self) -> &SuperclusterOptions;
+
+    fn projection(&self) -> &dyn Projection;
+
+    /// The input point at position `idx`, in the user's original coordinate system.
+    fn point(&self, idx: usize) -> (f64, f64);
+
+    /// The id of the input point at position `idx`.
+    fn point_id(&self, idx: usize) -> u64;
+
+    /// The position of the input point with the given id.
+    fn point_idx(&self, point_id: u64) -> Option<usize>;
+
+    /// The clusters at `zoom`, if it is between `min_zoom` and `max_zoom + 1`.
+    fn zoom_level(&self, zoom: usize) -> Option<&Self::Level>;
+
+    /// The id the cluster with the given public id is stored with, if any. See
+    /// [ClusterIndex::item_id].
+    fn stored_cluster_id(&self, cluster_id: ClusterId) -> Option<ClusterId>;
+
+    fn clusters(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        let projection = self.projection();
+        let [world_min_x, world_min_y, world_max_x, world_max_y] = projection.bounds();
+
+        let (mut min_x, mut max_x) = if projection.periodic_x() {
+            let world_width = world_max_x - world_min_x;
+            let min_x =
+                ((min_x - world_min_x) % world_width + world_width) % world_width + world_min_x;
+            let max_x = if max_x == world_max_x {
+                world_max_x
+            } else {
+                ((max_x - world_min_x) % world_width + world_width) % world_width + world_min_x
+            };
+            (min_x, max_x)
+        } else {
+            (
+                min_x.clamp(world_min_x, world_max_x),
+                max_x.clamp(world_min_x, world_max_x),
+            )
+        };
+        let min_y = min_y.clamp(world_min_y, world_max_y);
+        let max_y = max_y.clamp(world_min_y, world_max_y);
+
+        if max_x - min_x >= world_max_x - world_min_x {
+            min_x = world_min_x;
+            max_x = world_max_x;
+        } else if min_x > max_x {
+            // Unless x is periodic, a reversed bounding box contains nothing
+            if !projection.periodic_x() {
+                return vec![];
+            }
+            let mut eastern_hem = self.clusters(min_x, min_y, world_max_x, max_y, zoom);
+            let mut western_hem = self.clusters(world_min_x, min_y, max_x, max_y, zoom);
+            eastern_hem.append(&mut western_hem);
+            return eastern_hem;
+        }
+
+        let level = self.zoom_level(self.clamp_zoom(zoom)).unwrap();
+
+        let [proj_min_x, proj_min_y, proj_max_x, proj_max_y] =
+            projection.project_bbox(min_x, min_y, max_x, max_y);
+        let ids = level.range(
+            N::from_f64(proj_min_x),
+            N::from_f64(proj_min_y),
+            N::from_f64(proj_max_x),
+            N::from_f64(proj_max_y),
+        );
+
+        let mut clusters = Vec::with_capacity(ids.len());
+        for id in ids {
+            clusters.push(self.cluster_info(level, id));
+        }
+
+        if !projection.axis_aligned() {
+            clusters.retain(|cluster| {
+                (min_x..=max_x).contains(&cluster.x()) && (min_y..=max_y).contains(&cluster.y())
+            });
+        }
+
+        clusters
+    }
+
+    fn clusters_in_polygon(
+        &self,
+        exterior: &[(f64, f64)],
+        interiors: &[&[(f64, f64)]],
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        let projection = self.projection();
+        let [world_min_x, _, world_max_x, _] = projection.bounds();
+        let world_width = projection.periodic_x().then_some(world_max_x - world_min_x);
+
+        let polygon = PolygonFilter::new(exterior, interiors, world_width);
+        let Some([min_x, min_y, max_x, max_y]) = polygon.bbox() else {
+            return vec![];
+        };
+        let mut clusters = self.clusters(min_x, min_y, max_x, max_y, zoom);
+        clusters.retain(|cluster| polygon.contains(cluster.x(), cluster.y()));
+        clusters
+    }
+
+    #[cfg(feature = "geo-types")]
+    fn clusters_in_geo_polygon(
+        &self,
+        polygon: &geo_types::Polygon<f64>,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        let ring = |line: &geo_types::LineString<f64>| -> Vec<(f64, f64)> {
+            line.coords().map(|coord| (coord.x, coord.y)).collect()
+        };
+        let interiors: Vec<Vec<(f64, f64)>> = polygon.interiors().iter().map(ring).collect();
+        let interiors: Vec<&[(f64, f64)]> = interiors.iter().map(Vec::as_slice).collect();
+        self.clusters_in_polygon(&ring(polygon.exterior()), &interiors, zoom)
+    }
+
+    fn children(&self, cluster_id: ClusterId) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        let cluster_id = self.resolve_cluster(cluster_id)?;
+        let (level, children) = self.child_indices(cluster_id)?;
+        Ok(children
+            .into_iter()
+            .map(|i| self.cluster_info(level, i))
+            .collect())
+    }
+
+    /// The zoom level of the children of the cluster stored as `cluster_id`, and their indices
+    /// within it.
+    fn child_indices(
+        &self,
+        cluster_id: ClusterId,
+    ) -> Result<(&Self::Level, Vec<usize>), SuperclusterError> {
+        // Clusters always originate at a zoom above 0
+        if cluster_id.get_origin_zoom() == 0 {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        let origin_id = cluster_id.get_origin_idx();
+        let origin_zoom = cluster_id.get_origin_zoom();
+
+        let level = match self.zoom_level(origin_zoom) {
+            Some(level) => level,
+            None => return Err(SuperclusterError::NoClusterFound),
+        };
+
+        if origin_id >= level.len() {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        let r = self.options().zoom_radius(origin_zoom - 1);
+        let (x, y) = level.anchor(origin_id);
+        let ids = level.within(x, y, N::from_f64(r));
+        let mut children = vec![];
+
+        for id in ids {
+            if level
+                .parent_id(id)
+                .is_some_and(|parent_id| parent_id == cluster_id)
+            {
+                children.push(id);
+            }
+        }
+
+        if children.is_empty() {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        Ok((level, children))
+    }
+
+    fn leaves(
+        &self,
+        cluster_id: ClusterId,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        let limit = limit.unwrap_or(10);
+        let offset = offset.unwrap_or(0);
+
+        let cluster_id = self.resolve_cluster(cluster_id)?;
+        let mut leaves = vec![];
+        self.append_leaves(&mut leaves, cluster_id, limit, offset, 0)?;
+
+        Ok(leaves)
+    }
+
+    fn tile(&self, z: usize, x: usize, y: usize) -> Vec<TileFeature<P>> {
+        let level = self.zoom_level(self.clamp_zoom(z)).unwrap();
+        let options = self.options();
+        let z2 = f64::powi(2.0, z as i32);
+        let p = options.radius / options.extent;
+        let (x, y) = (x as f64, y as f64);
+        let top = N::from_f64((y - p) / z2);
+        let bottom = N::from_f64((y + 1.0 + p) / z2);
+
+        let mut features = vec![];
+        self.append_tile_features(
+            &mut features,
+            level,
+            level.range(
+                N::from_f64((x - p) / z2),
+                top,
+                N::from_f64((x + 1.0 + p) / z2),
+                bottom,
+            ),
+            x,
+            y,
+            z2,
+        );
+
+        // Include features from the far side of the antimeridian in the tile buffer
+        if x == 0.0 && self.projection().wraps() {
+            self.append_tile_features(
+                &mut features,
+                level,
+                level.range(N::from_f64(1.0 - p / z2), top, N::one(), bottom),
+                z2,
+                y,
+                z2,
+            );
+        }
+        if x == z2 - 1.0 && self.projection().wraps() {
+            self.append_tile_features(
+                &mut features,
+                level,
+                level.range(N::zero(), top, N::from_f64(p / z2), bottom),
+                -1.0,
+                y,
+                z2,
+            );
+        }
+
+        features
+    }
+
+    fn cluster_bounds(&self, cluster_id: ClusterId) -> Result<[f64; 4], SuperclusterError> {
+        let cluster_id = self.resolve_cluster(cluster_id)?;
+        if cluster_id.get_origin_zoom() == 0 {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        // The bounds of the points are stored on the cluster itself
+        let zoom = cluster_id.get_origin_zoom() - 1;
+        let level = self
+            .zoom_level(zoom)
+            .ok_or(SuperclusterError::NoClusterFound)?;
+        match self.find_cluster(zoom, cluster_id) {
+            Some(i) if level.num_points(i) > 0 => Ok(level.bounds(i)),
+            _ => Err(SuperclusterError::NoClusterFound),
+        }
+    }
+
+    fn cluster_hull(&self, cluster_id: ClusterId) -> Result<Vec<(f64, f64)>, SuperclusterError> {
+        let cluster_id = self.resolve_cluster(cluster_id)?;
+        if cluster_id.get_origin_zoom() == 0 {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        let zoom = cluster_id.get_origin_zoom() - 1;
+        let level = self
+            .zoom_level(zoom)
+            .ok_or(SuperclusterError::NoClusterFound)?;
+        match self.find_cluster(zoom, cluster_id) {
+            Some(i) if level.num_points(i) > 0 => {
+                let (x, y) = level.position(i);
+                let (x, _) = self.projection().unproject(x.as_f64(), y.as_f64());
+                self.merged_hull(cluster_id, x)
+            }
+            _ => Err(SuperclusterError::NoClusterFound),
+        }
+    }
+
+    fn point_cluster(
+        &self,
+        point_id: u64,
+        zoom: usize,
+    ) -> Result<ClusterInfo<P>, SuperclusterError> {
+        let zoom = self.clamp_zoom(zoom);
+        let mut ancestors = self.point_ancestors(point_id, zoom)?;
+        let (_, idx) = ancestors.pop().unwrap();
+        Ok(self.cluster_info(self.zoom_level(zoom).unwrap(), idx))
+    }
+
+    fn ancestors(&self, point_id: u64) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        let mut ancestors: Vec<ClusterInfo<P>> = vec![];
+        for (zoom, idx) in self.point_ancestors(point_id, self.options().min_zoom)? {
+            let level = self.zoom_level(zoom).unwrap();
+            let id = self.item_id(level, idx);
+            // Clusters that are not merged into another cluster are copied to the next zoom
+            if id.is_cluster() && ancestors.last().is_none_or(|last| last.id() != id) {
+                ancestors.push(self.cluster_info(level, idx));
+            }
+        }
+        Ok(ancestors)
+    }
+
+    /// The index of the item representing the point `point_id` at each zoom from
+    /// `max_zoom + 1` down to `min_zoom`.
+    fn point_ancestors(
+        &self,
+        point_id: u64,
+        min_zoom: usize,
+    ) -> Result<Vec<(usize, usize)>, SuperclusterError> {
+        let mut zoom = self.options().max_zoom + 1;
+        let idx = self
+            .point_idx(point_id)
+            .filter(|idx| self.zoom_level(zoom).unwrap().num_points(*idx) > 0)
+            .ok_or(SuperclusterError::NoPointFound)?;
+
+        let mut ancestors = vec![(zoom, idx)];
+        while zoom > min_zoom {
+            let idx = self
+                .find_parent(zoom, ancestors.last().unwrap().1)
+                .ok_or(SuperclusterError::NoClusterFound)?;
+            zoom -= 1;
+            ancestors.push((zoom, idx));
+        }
+        Ok(ancestors)
+    }
+
+    /// Find the index within `zoom - 1` of the item representing the item at index `idx` of
+    /// `zoom`, which is either its parent cluster or its unclustered copy.
+    fn find_parent(&self, zoom: usize, idx: usize) -> Option<usize> {
+        let level = self.zoom_level(zoom)?;
+        match level.parent_id(idx) {
+            Some(parent_id) => self.find_cluster(zoom - 1, parent_id),
+            None => {
+                let (x, y) = level.anchor(idx);
+                self.find_copy(zoom - 1, level.source_id(idx), x, y)
+            }
+        }
+    }
+
+    /// Find the index of the cluster `cluster_id` within `zoom`.
+    fn find_cluster(&self, zoom: usize, cluster_id: ClusterId) -> Option<usize> {
+        let origin_level = self.zoom_level(zoom + 1)?;
+        let origin_idx = cluster_id.get_origin_idx();
+        if origin_idx >= origin_level.len() {
+            return None;
+        }
+        let (x, y) = origin_level.anchor(origin_idx);
+
+        // The cluster was indexed at the centroid of its children at the time, which are all
+        // within the cluster radius of the origin. Allow for rounding of the centroid.
+        let r = N::from_f64(2.0 * self.options().zoom_radius(zoom));
+        let level = self.zoom_level(zoom)?;
+        level
+            .within(x, y, r)
+            .into_iter()
+            .find(|i| level.source_id(*i) == cluster_id)
+    }
+
+    /// The convex hull of the cluster stored as `cluster_id`, merged from the hulls of its child
+    /// clusters and the positions of its child points.
+    ///
+    /// If x is periodic, vertices are unwrapped to within half a world of `center_x`, so that the
+    /// hull of a cluster crossing the edge of the world doesn't span the whole world.
+    fn merged_hull(
+        &self,
+        cluster_id: ClusterId,
+        center_x: f64,
+    ) -> Result<Vec<(f64, f64)>, SuperclusterError> {
+        let (level, children) = self.child_indices(cluster_id)?;
+        let mut points = vec![];
+        for child in children {
+            if level.num_points(child) > 1 {
+                points.append(&mut self.merged_hull(level.source_id(child), center_x)?);
+            } else {
+                points.push(self.point(level.source_id(child).as_usize()));
+            }
+        }
+
+        let projection = self.projection();
+        if projection.periodic_x() {
+            let [world_min_x, _, world_max_x, _] = projection.bounds();
+            let world_width = world_max_x - world_min_x;
+            for (x, _) in &mut points {
+                *x -= world_width * ((*x - center_x) / world_width).round();
+            }
+        }
+
+        Ok(convex_hull(points))
+    }
+
+    /// Find the index of the unclustered copy of an item within `zoom`.
+    fn find_copy(&self, zoom: usize, source_id: ClusterId, x: N, y: N) -> Option<usize> {
+        let level = self.zoom_level(zoom)?;
+        level
+            .within(x, y, N::zero())
+            .into_iter()
+            .find(|i| level.source_id(*i) == source_id)
+    }
+
+    fn hierarchy<'a>(&'a self) -> impl Iterator<Item = HierarchyEntry> + 'a
+    where
+        Self::Level: 'a,
+    {
+        let options = self.options();
+        (options.min_zoom..=options.max_zoom + 1).flat_map(move |zoom| {
+            let level = self.zoom_level(zoom).unwrap();
+            (0..level.len())
+                .filter(|i| level.num_points(*i) > 0)
+                .map(move |i| self.hierarchy_entry(zoom, level, i))
+        })
+    }
+
+    /// Describe the item at index `i` of `level`, at `zoom`, for [ClusterIndex::hierarchy].
+    fn hierarchy_entry(&self, zoom: usize, level: &Self::Level, i: usize) -> HierarchyEntry {
+        let num_points = level.num_points(i);
+        let (x, y) = if num_points > 1 {
+            let (x, y) = level.position(i);
+            self.projection().unproject(x.as_f64(), y.as_f64())
+        } else {
+            self.point(level.source_id(i).as_usize())
+        };
+        HierarchyEntry::new(
+            zoom,
+            self.item_id(level, i),
+            level.parent_id(i).and_then(|parent_id| {
+                let parent_level = self.zoom_level(zoom - 1)?;
+                let parent_idx = self.find_cluster(zoom - 1, parent_id)?;
+                Some(self.item_id(parent_level, parent_idx))
+            }),
+            x,
+            y,
+            num_points,
+        )
+    }
+
+    fn nearest(&self, x: f64, y: f64, zoom: usize, max_distance: f64) -> Option<ClusterInfo<P>> {
+        let level = self.zoom_level(self.clamp_zoom(zoom)).unwrap();
+        let ids = self.displayed_near(level, x, y, zoom, max_distance);
+        ids.first().map(|(_, id)| self.cluster_info(level, *id))
+    }
+
+    fn clusters_within(&self, x: f64, y: f64, distance: f64, zoom: usize) -> Vec<ClusterInfo<P>> {
+        let level = self.zoom_level(self.clamp_zoom(zoom)).unwrap();
+        self.displayed_near(level, x, y, zoom, distance)
+            .into_iter()
+            .map(|(_, id)| self.cluster_info(level, id))
+            .collect()
+    }
+
+    /// Find the items of `level` whose displayed position is within `distance` pixels at `zoom`,
+    /// clamped like the zoom of `level`, of `(x, y)`, with their squared distance in projected
+    /// units, from nearest to farthest.
+    fn displayed_near(
+        &self,
+        level: &Self::Level,
+        x: f64,
+        y: f64,
+        zoom: usize,
+        distance: f64,
+    ) -> Vec<(f64, usize)> {
+        let projection = self.projection();
+        let (qx, qy) = projection.project(x, y);
+        let r = self
+            .options()
+            .pixels_to_distance(distance, self.clamp_zoom(zoom));
+
+        // Also search across the antimeridian
+        let mut offsets = vec![0.0];
+        if projection.wraps() {
+            if qx - r < 0.0 {
+                offsets.push(1.0);
+            }
+            if qx + r > 1.0 {
+                offsets.push(-1.0);
+            }
+        }
+
+        let mut near = vec![];
+        for offset in offsets {
+            let qx = qx + offset;
+            for id in level.displayed_within(N::from_f64(qx), N::from_f64(qy), N::from_f64(r)) {
+                let (px, py) = level.position(id);
+                let (dx, dy) = (px.as_f64() - qx, py.as_f64() - qy);
+                near.push((dx * dx + dy * dy, id));
+            }
+        }
+
+        // An item may be found on both sides of the antimeridian if the distance is large
+        near.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.total_cmp(&b.0)));
+        near.dedup_by_key(|(_, id)| *id);
+        near.sort_by(|a, b| a.0.total_cmp(&b.0));
+        near
+    }
+
+    fn expansion_zoom(&self, cluster_id: ClusterId) -> Result<usize, SuperclusterError> {
+        let mut cluster_id = self.resolve_cluster(cluster_id)?;
+        if cluster_id.get_origin_zoom() == 0 {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+
+        let mut expansion_zoom = cluster_id.get_origin_zoom() - 1;
+        while expansion_zoom <= self.options().max_zoom {
+            let (level, children) = self.child_indices(cluster_id)?;
+            expansion_zoom += 1;
+            if children.len() != 1 || !level.source_id(children[0]).is_cluster() {
+                break;
+            }
+            cluster_id = level.source_id(children[0]);
+        }
+
+        Ok(expansion_zoom)
+    }
+
+    /// Describe the item at index `i` of `level`.
+    fn cluster_info(&self, level: &Self::Level, i: usize) -> ClusterInfo<P> {
+        let num_points = level.num_points(i);
+
+        // If there's more than one point in this cluster, group them.
+        if num_points > 1 {
+            let (x, y) = level.position(i);
+            let (x, y) = self.projection().unproject(x.as_f64(), y.as_f64());
+            ClusterInfo::new_cluster(
+                self.item_id(level, i),
+                x,
+                y,
+                num_points,
+                level.weight(i),
+                level.category(i),
+                level.properties(i),
+            )
+            .with_bounds(level.bounds(i))
+        } else {
+            let (x, y) = self.point(level.source_id(i).as_usize());
+            ClusterInfo::new_leaf(
+                self.item_id(level, i),
+                x,
+                y,
+                level.weight(i),
+                level.category(i),
+                level.properties(i),
+            )
+        }
+    }
+
+    /// The public id of the item at index `i` of `level`.
+    ///
+    /// Items refer to input points by their position, which is replaced by the id of the point.
+    /// Clusters are stored with the index of their origin item within the origin zoom. Their
+    /// public id has the smallest id among their points instead, so that it doesn't depend on the
+    /// positions of other items.
+    fn item_id(&self, level: &Self::Level, i: usize) -> ClusterId {
+        let id = level.source_id(i);
+        if id.is_cluster() {
+            ClusterId::new(self.point_id(level.min_point(i)), id.get_origin_zoom() - 1)
+        } else {
+            ClusterId::from(self.point_id(id.as_usize()))
+        }
+    }
+
+    /// The id the cluster with the given public id is stored with.
+    fn resolve_cluster(&self, cluster_id: ClusterId) -> Result<ClusterId, SuperclusterError> {
+        // Leaves have no children
+        if !cluster_id.is_cluster() {
+            return Err(SuperclusterError::NoClusterFound);
+        }
+        self.stored_cluster_id(cluster_id)
+            .ok_or(SuperclusterError::NoClusterFound)
+    }
+
+    /// The public and stored ids of each cluster that has not been removed, ordered by public id.
+    ///
+    /// Each cluster is listed once, at the zoom it was formed at. If the same public id was given
+    /// to several clusters, which can happen after updates, the last one formed is listed.
+    fn cluster_ids(&self) -> Vec<(ClusterId, ClusterId)> {
+        let options = self.options();
+        let mut cluster_ids = BTreeMap::new();
+        for zoom in options.min_zoom..=options.max_zoom {
+            let level = self.zoom_level(zoom).unwrap();
+            for i in 0..level.len() {
+                let id = level.source_id(i);
+                if id.is_cluster() && id.get_origin_zoom() == zoom + 1 && level.num_points(i) > 0 {
+                    cluster_ids.insert(self.item_id(level, i), id);
+                }
+            }
+        }
+        cluster_ids.into_iter().collect()
+    }
+
+    /// Append the leaves of the cluster stored as `cluster_id` to `result`.
+    fn append_leaves(
+        &self,
+        result: &mut Vec<ClusterInfo<P>>,
+        cluster_id: ClusterId,
+        limit: usize,
+        offset: usize,
+        skipped: usize,
+    ) -> Result<usize, SuperclusterError> {
+        let (level, children) = self.child_indices(cluster_id)?;
+
+        let mut skipped = skipped;
+
+        for child in children {
+            let count = level.num_points(child);
+            if count > 1 {
+                if skipped + count <= offset {
+                    // skip the whole cluster
+                    skipped += count;
+                } else {
+                    // enter the cluster
+                    let child_id = level.source_id(child);
+                    skipped = self.append_leaves(result, child_id, limit, offset, skipped)?;
+                    // exit the cluster
+                }
+                skipped += 1;
+            } else if skipped < offset {
+                // skip a single point
+                skipped += 1;
+            } else {
+                // add a single point
+                result.push(self.cluster_info(level, child));
+            }
+
+            if result.len() == limit {
+                break;
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    // This is derived from Supercluster._addTileFeatures in the original JS implementation
+    fn append_tile_features(
+        &self,
+        result: &mut Vec<TileFeature<P>>,
+        level: &Self::Level,
+        ids: Vec<usize>,
+        x: f64,
+        y: f64,
+        z2: f64,
+    ) {
+        let extent = self.options().extent;
+        for id in ids {
+            let num_points = level.num_points(id);
+            let (cx, cy) = level.position(id);
+            let px = (extent * (cx.as_f64() * z2 - x)).round() as i64;
+            let py = (extent * (cy.as_f64() * z2 - y)).round() as i64;
+            result.push(TileFeature::new(
+                self.item_id(level, id),
+                px,
+                py,
+                num_points > 1,
+                num_points,
+                level.category(id),
+                level.properties(id),
+            ));
+        }
+    }
+
+    fn clamp_zoom(&self, zoom: usize) -> usize {
+        let options = self.options();
+        zoom.clamp(options.min_zoom, options.max_zoom + 1)
+    }
+}
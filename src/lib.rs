@@ -1,17 +1,35 @@
 #![doc = include_str!("../README.md")]
 
+mod aggregator;
 mod builder;
 mod cluster;
 pub mod error;
+mod format;
+mod hierarchy;
+mod index;
 mod options;
+mod polygon;
+mod projection;
 mod supercluster;
+mod supercluster_ref;
+mod tile;
 mod tree;
+mod r#type;
+mod update;
 pub(crate) mod util;
 
-pub use builder::SuperclusterBuilder;
-pub use cluster::{ClusterData, ClusterId, ClusterInfo};
+pub use aggregator::ClusterAggregator;
+pub use builder::{CoordinatePolicy, SuperclusterBuilder};
+pub use cluster::{ClusterData, ClusterId, ClusterIdKind, ClusterInfo};
+pub use hierarchy::HierarchyEntry;
 pub use options::SuperclusterOptions;
+pub use projection::{
+    Equirectangular, MercatorUnits, Planar, PolarStereographic, Projection, WebMercator,
+};
+pub use r#type::ClusterFloat;
 pub use supercluster::Supercluster;
+pub use supercluster_ref::SuperclusterRef;
+pub use tile::TileFeature;
 
 #[cfg(test)]
 pub(crate) mod test;
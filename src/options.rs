@@ -1,3 +1,6 @@
+use crate::cluster::ClusterId;
+use crate::error::SuperclusterError;
+
 /// Options for Supercluster generation
 #[derive(Debug, Clone, Copy)]
 pub struct SuperclusterOptions {
@@ -13,6 +16,8 @@ pub struct SuperclusterOptions {
 
     /// Minimum number of points to form a cluster.
     ///
+    /// For weighted points, this is the minimum total weight of a cluster.
+    ///
     /// Defaults to `2`.
     pub min_points: usize,
 
@@ -33,6 +38,10 @@ pub struct SuperclusterOptions {
 }
 
 impl SuperclusterOptions {
+    /// The largest supported [`max_zoom`][Self::max_zoom], limited by the bits available for
+    /// zooms in a [ClusterId].
+    pub const MAX_ZOOM: usize = ClusterId::MAX_ORIGIN_ZOOM - 1;
+
     pub fn new() -> Self {
         Default::default()
     }
@@ -55,6 +64,44 @@ impl SuperclusterOptions {
     pub fn with_node_size(self, node_size: usize) -> Self {
         SuperclusterOptions { node_size, ..self }
     }
+
+    /// Check that these options can be used to build an index.
+    ///
+    /// `min_zoom` must not be greater than `max_zoom`, which must be at most
+    /// [`MAX_ZOOM`][Self::MAX_ZOOM]. `radius` and `extent` must be positive and finite, and
+    /// `node_size` must be between 2 and 65535.
+    pub fn validate(&self) -> Result<(), SuperclusterError> {
+        if self.min_zoom > self.max_zoom {
+            return Err(SuperclusterError::InvalidZoomRange {
+                min_zoom: self.min_zoom,
+                max_zoom: self.max_zoom,
+            });
+        }
+        if self.max_zoom > Self::MAX_ZOOM {
+            return Err(SuperclusterError::MaxZoomTooLarge(self.max_zoom));
+        }
+        if !(self.radius.is_finite() && self.radius > 0.0) {
+            return Err(SuperclusterError::InvalidRadius(self.radius));
+        }
+        if !(self.extent.is_finite() && self.extent > 0.0) {
+            return Err(SuperclusterError::InvalidExtent(self.extent));
+        }
+        if !(2..=65535).contains(&self.node_size) {
+            return Err(SuperclusterError::InvalidNodeSize(self.node_size));
+        }
+        Ok(())
+    }
+
+    /// The cluster radius at the given zoom, in projected units of the unit square.
+    pub(crate) fn zoom_radius(&self, zoom: usize) -> f64 {
+        self.pixels_to_distance(self.radius, zoom)
+    }
+
+    /// Convert a distance in pixels at the given zoom, relative to the tile extent like
+    /// [`radius`][Self::radius], to projected units of the unit square.
+    pub(crate) fn pixels_to_distance(&self, pixels: f64, zoom: usize) -> f64 {
+        pixels / (self.extent * f64::powi(2.0, zoom as i32))
+    }
 }
 
 impl Default for SuperclusterOptions {
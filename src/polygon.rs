@@ -0,0 +1,173 @@
+/// A polygon used to filter query results, in the user's original coordinate system.
+///
+/// If the projection wraps around the antimeridian, an edge between two vertices more than half
+/// the world width apart in x is taken to cross the antimeridian, so that a polygon can be drawn
+/// across it with vertices on both sides.
+pub(crate) struct PolygonFilter {
+    /// The exterior ring, followed by any holes, with x values unwrapped to be continuous.
+    rings: Vec<Vec<(f64, f64)>>,
+
+    /// The width of the world if the projection wraps around the antimeridian.
+    world_width: Option<f64>,
+}
+
+impl PolygonFilter {
+    /// Create a filter from an exterior ring and holes. Rings may or may not repeat their first
+    /// vertex at the end.
+    pub(crate) fn new(
+        exterior: &[(f64, f64)],
+        interiors: &[&[(f64, f64)]],
+        world_width: Option<f64>,
+    ) -> Self {
+        let mut rings = vec![unwrap_ring(exterior, world_width)];
+        let center = rings[0].first().map_or(0.0, |(x, _)| *x);
+        for interior in interiors {
+            let mut ring = unwrap_ring(interior, world_width);
+
+            // Align holes with the exterior ring
+            if let (Some(width), Some((x, _))) = (world_width, ring.first()) {
+                let shift = ((center - x) / width).round() * width;
+                ring.iter_mut().for_each(|(x, _)| *x += shift);
+            }
+            rings.push(ring);
+        }
+
+        Self { rings, world_width }
+    }
+
+    /// The bounding box of the exterior ring, or `None` if it has fewer than 3 vertices.
+    ///
+    /// The x values may extend past the antimeridian.
+    pub(crate) fn bbox(&self) -> Option<[f64; 4]> {
+        let exterior = &self.rings[0];
+        if exterior.len() < 3 {
+            return None;
+        }
+
+        let mut bbox = [
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        for (x, y) in exterior {
+            bbox = [
+                bbox[0].min(*x),
+                bbox[1].min(*y),
+                bbox[2].max(*x),
+                bbox[3].max(*y),
+            ];
+        }
+        Some(bbox)
+    }
+
+    /// Whether `(x, y)` is inside the exterior ring and outside of every hole.
+    pub(crate) fn contains(&self, x: f64, y: f64) -> bool {
+        let shifts = match self.world_width {
+            Some(width) => vec![0.0, -width, width],
+            None => vec![0.0],
+        };
+        shifts.into_iter().any(|shift| {
+            let x = x + shift;
+            ring_contains(&self.rings[0], x, y)
+                && !self.rings[1..].iter().any(|ring| ring_contains(ring, x, y))
+        })
+    }
+}
+
+/// Shift the x values of `ring` by multiples of `world_width`, so that no edge is longer than
+/// half the world width in x. An edge of exactly half the world width is left as is.
+fn unwrap_ring(ring: &[(f64, f64)], world_width: Option<f64>) -> Vec<(f64, f64)> {
+    let Some(width) = world_width else {
+        return ring.to_vec();
+    };
+
+    let mut unwrapped: Vec<(f64, f64)> = Vec::with_capacity(ring.len());
+    for (x, y) in ring {
+        let x = match unwrapped.last() {
+            Some((prev_x, _)) if (prev_x - x).abs() > width / 2.0 => {
+                x + ((prev_x - x) / width).round() * width
+            }
+            _ => *x,
+        };
+        unwrapped.push((x, *y));
+    }
+    unwrapped
+}
+
+/// Whether `(x, y)` is inside `ring`, using the even-odd rule.
+fn ring_contains(ring: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for (i, (xi, yi)) in ring.iter().enumerate() {
+        let (xj, yj) = ring[j];
+        if (*yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let hole: &[(f64, f64)] = &[(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0), (4.0, 4.0)];
+        let polygon = PolygonFilter::new(&square, &[hole], None);
+        assert_eq!(polygon.bbox(), Some([0.0, 0.0, 10.0, 10.0]));
+        assert!(polygon.contains(1.0, 1.0));
+        assert!(polygon.contains(9.0, 5.0));
+        assert!(!polygon.contains(5.0, 5.0));
+        assert!(!polygon.contains(11.0, 5.0));
+        assert!(!polygon.contains(-1.0, 5.0));
+    }
+
+    #[test]
+    fn test_antimeridian() {
+        let ring = [
+            (170.0, -10.0),
+            (-170.0, -10.0),
+            (-170.0, 10.0),
+            (170.0, 10.0),
+        ];
+        let polygon = PolygonFilter::new(&ring, &[], Some(360.0));
+        assert_eq!(polygon.bbox(), Some([170.0, -10.0, 190.0, 10.0]));
+        assert!(polygon.contains(175.0, 0.0));
+        assert!(polygon.contains(-175.0, 0.0));
+        assert!(!polygon.contains(0.0, 0.0));
+        assert!(!polygon.contains(165.0, 0.0));
+
+        // An edge of half the world width does not cross the antimeridian
+        let ring = [
+            (-180.0, -10.0),
+            (0.0, -10.0),
+            (180.0, -10.0),
+            (180.0, 10.0),
+            (-180.0, 10.0),
+        ];
+        let polygon = PolygonFilter::new(&ring, &[], Some(360.0));
+        assert_eq!(polygon.bbox(), Some([-180.0, -10.0, 180.0, 10.0]));
+
+        // Without wrapping, the same ring spans most of the world instead
+        let ring = [
+            (170.0, -10.0),
+            (-170.0, -10.0),
+            (-170.0, 10.0),
+            (170.0, 10.0),
+        ];
+        let polygon = PolygonFilter::new(&ring, &[], None);
+        assert!(polygon.contains(0.0, 0.0));
+        assert!(!polygon.contains(175.0, 0.0));
+    }
+
+    #[test]
+    fn test_degenerate() {
+        let polygon = PolygonFilter::new(&[(0.0, 0.0), (1.0, 1.0)], &[], None);
+        assert_eq!(polygon.bbox(), None);
+        assert!(!polygon.contains(0.5, 0.5));
+    }
+}
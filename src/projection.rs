@@ -0,0 +1,464 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::fmt::Debug;
+
+use crate::util::{latitude_to_y, longitude_to_x, x_to_longitude, y_to_latitude};
+
+/// Half the circumference of the earth in Web Mercator (EPSG:3857) meters.
+const HALF_CIRCUMFERENCE: f64 = 20037508.342789244;
+
+/// A projection from the coordinate system of input points to the unit square used for
+/// clustering.
+///
+/// Points are clustered in projected space, where the world is mapped onto `[0..1]` in both
+/// dimensions and `(0, 0)` is the top-left corner. Cluster radii and tiles are computed relative to
+/// this unit square.
+pub trait Projection: Debug + Send + Sync {
+    /// Project a coordinate from the input coordinate system to the unit square.
+    fn project(&self, x: f64, y: f64) -> (f64, f64);
+
+    /// Project a coordinate from the unit square back to the input coordinate system.
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64);
+
+    /// The extent of valid input coordinates, as `[min_x, min_y, max_x, max_y]`.
+    ///
+    /// Query bounding boxes are clamped to this extent.
+    fn bounds(&self) -> [f64; 4];
+
+    /// Whether the x axis of the unit square wraps around, as it does at the antimeridian in
+    /// [WebMercator].
+    ///
+    /// If true, tiles on the edge of the world include features from the opposite edge, and
+    /// distance queries search across the edge.
+    fn wraps(&self) -> bool {
+        false
+    }
+
+    /// Whether the x coordinate of input points is periodic over the width of
+    /// [`bounds`][Self::bounds], as longitude is, even if the x axis of the unit square doesn't
+    /// [wrap][Self::wraps]. Defaults to [`wraps`][Self::wraps].
+    ///
+    /// If true, query bounding boxes and polygons crossing the edge of the bounds are split or
+    /// unwrapped, and [`CoordinatePolicy::Clamp`][crate::CoordinatePolicy::Clamp] wraps x rather
+    /// than clamping it.
+    fn periodic_x(&self) -> bool {
+        self.wraps()
+    }
+
+    /// A number identifying the kind of this projection, stored with its
+    /// [`bounds`][Self::bounds] by [`Supercluster::to_bytes`][crate::Supercluster::to_bytes], so
+    /// that an index can't be loaded with a different projection.
+    ///
+    /// The built-in projections use `1` to `4`. Custom projections default to `0`, and may
+    /// return any other value to tell themselves apart from each other.
+    fn tag(&self) -> u64 {
+        0
+    }
+
+    /// Whether axis-aligned bounding boxes in input coordinates are projected to axis-aligned
+    /// bounding boxes in the unit square.
+    ///
+    /// If false, query results are additionally filtered in input coordinates.
+    fn axis_aligned(&self) -> bool {
+        true
+    }
+
+    /// Project a bounding box in input coordinates to a bounding box in the unit square which
+    /// contains it, as `[min_x, min_y, max_x, max_y]`.
+    ///
+    /// The default implementation projects the corners of the bounding box.
+    fn project_bbox(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> [f64; 4] {
+        // NOTE! the y axis may be flipped in projected coordinates. For example in spherical
+        // mercator, max_lat is projected to min_y and min_lat is projected to max_y.
+        let (x0, y0) = self.project(min_x, min_y);
+        let (x1, y1) = self.project(max_x, max_y);
+        [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]
+    }
+}
+
+/// The units of points projected with [WebMercator].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MercatorUnits {
+    /// Longitude and latitude in degrees (EPSG:4326). Points are projected to Web Mercator when
+    /// building the index.
+    #[default]
+    Degrees,
+
+    /// Web Mercator meters (EPSG:3857).
+    Meters,
+
+    /// Web Mercator normalized to the `[0..1]` range, where `(0, 0)` is the north-west corner and
+    /// `(1, 1)` is the south-east corner of the world.
+    Normalized,
+}
+
+/// The Web Mercator projection (EPSG:3857), as used by the original JS implementation.
+///
+/// Input points may be longitude and latitude, or already projected to Web Mercator. Latitudes
+/// beyond roughly ±85.05° are clamped to the edge of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WebMercator {
+    units: MercatorUnits,
+}
+
+impl WebMercator {
+    /// Construct a new Web Mercator projection for input points in the given units.
+    pub fn new(units: MercatorUnits) -> Self {
+        Self { units }
+    }
+
+    /// The units of input points.
+    pub fn units(&self) -> MercatorUnits {
+        self.units
+    }
+}
+
+impl Projection for WebMercator {
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        match self.units {
+            MercatorUnits::Degrees => (longitude_to_x(x), latitude_to_y(y)),
+            MercatorUnits::Meters => (
+                x / (2.0 * HALF_CIRCUMFERENCE) + 0.5,
+                0.5 - y / (2.0 * HALF_CIRCUMFERENCE),
+            ),
+            MercatorUnits::Normalized => (x, y),
+        }
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        match self.units {
+            MercatorUnits::Degrees => (x_to_longitude(x), y_to_latitude(y)),
+            MercatorUnits::Meters => (
+                (x - 0.5) * 2.0 * HALF_CIRCUMFERENCE,
+                (0.5 - y) * 2.0 * HALF_CIRCUMFERENCE,
+            ),
+            MercatorUnits::Normalized => (x, y),
+        }
+    }
+
+    fn bounds(&self) -> [f64; 4] {
+        match self.units {
+            MercatorUnits::Degrees => [-180.0, -90.0, 180.0, 90.0],
+            MercatorUnits::Meters => [
+                -HALF_CIRCUMFERENCE,
+                -HALF_CIRCUMFERENCE,
+                HALF_CIRCUMFERENCE,
+                HALF_CIRCUMFERENCE,
+            ],
+            MercatorUnits::Normalized => [0.0, 0.0, 1.0, 1.0],
+        }
+    }
+
+    fn wraps(&self) -> bool {
+        true
+    }
+
+    fn tag(&self) -> u64 {
+        1
+    }
+}
+
+/// The equirectangular projection (plate carrée) of longitude and latitude in degrees.
+///
+/// Unlike [WebMercator], this does not clamp latitudes near the poles. To keep distances in
+/// longitude and latitude comparable, the world is projected to the middle half of the unit
+/// square, with latitudes in `[0.25..0.75]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        (x / 360.0 + 0.5, 0.5 - y / 360.0)
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - 0.5) * 360.0, (0.5 - y) * 360.0)
+    }
+
+    fn bounds(&self) -> [f64; 4] {
+        [-180.0, -90.0, 180.0, 90.0]
+    }
+
+    fn wraps(&self) -> bool {
+        true
+    }
+
+    fn tag(&self) -> u64 {
+        2
+    }
+}
+
+/// The polar stereographic projection of longitude and latitude in degrees, centered on the north
+/// or south pole.
+///
+/// The hemisphere around the pole is projected to the unit square, with the pole in the center.
+/// Latitudes in the opposite hemisphere are clamped to the equator. Longitude is periodic, so query
+/// bounding boxes may cross the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolarStereographic {
+    north: bool,
+}
+
+impl PolarStereographic {
+    /// A polar stereographic projection centered on the north pole.
+    pub fn north() -> Self {
+        Self { north: true }
+    }
+
+    /// A polar stereographic projection centered on the south pole.
+    pub fn south() -> Self {
+        Self { north: false }
+    }
+
+    /// Whether this projection is centered on the north pole.
+    pub fn is_north(&self) -> bool {
+        self.north
+    }
+
+    /// The sign applied to latitudes and projected y values, such that the pole of this
+    /// projection is always treated as the north pole.
+    fn sign(&self) -> f64 {
+        if self.north {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Distance from the pole in the unit square, in the range `[0..0.5]` for the hemisphere.
+    fn radius(&self, lat: f64) -> f64 {
+        let lat = (lat * self.sign()).clamp(0.0, 90.0).to_radians();
+        // On a unit sphere, the equator is at a distance of 2 from the pole.
+        f64::tan(FRAC_PI_4 - lat / 2.0) / 2.0
+    }
+
+    /// The point on the unit square at the given distance from the pole and longitude.
+    fn point(&self, radius: f64, lon: f64) -> (f64, f64) {
+        let lon = lon.to_radians();
+        (
+            0.5 + radius * f64::sin(lon),
+            0.5 + radius * f64::cos(lon) * self.sign(),
+        )
+    }
+}
+
+impl Projection for PolarStereographic {
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        self.point(self.radius(y), normalize_longitude(x))
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let dx = x - 0.5;
+        let dy = (y - 0.5) * self.sign();
+        let radius = f64::hypot(dx, dy);
+        let lon = f64::atan2(dx, dy).to_degrees();
+        let lat = (FRAC_PI_2 - 2.0 * f64::atan(radius * 2.0)).to_degrees();
+        (lon, lat * self.sign())
+    }
+
+    fn bounds(&self) -> [f64; 4] {
+        if self.north {
+            [-180.0, 0.0, 180.0, 90.0]
+        } else {
+            [-180.0, -90.0, 180.0, 0.0]
+        }
+    }
+
+    fn periodic_x(&self) -> bool {
+        true
+    }
+
+    fn tag(&self) -> u64 {
+        3
+    }
+
+    fn axis_aligned(&self) -> bool {
+        false
+    }
+
+    /// A longitude-latitude bounding box is projected to a sector of an annulus around the pole.
+    /// Its bounding box is the bounding box of its corners and of the points where its outer arc
+    /// crosses the x or y axis.
+    fn project_bbox(&self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> [f64; 4] {
+        let inner = self.radius(min_y).min(self.radius(max_y));
+        let outer = self.radius(min_y).max(self.radius(max_y));
+
+        // Start the sector within [-180..180), so that the axes it crosses are found below
+        let width = (max_x - min_x).min(360.0);
+        let min_x = normalize_longitude(min_x);
+        let max_x = min_x + width;
+
+        let mut points = vec![
+            self.point(inner, min_x),
+            self.point(inner, max_x),
+            self.point(outer, min_x),
+            self.point(outer, max_x),
+        ];
+        for lon in [-180.0, -90.0, 0.0, 90.0, 180.0, 270.0, 360.0, 450.0] {
+            if (min_x..=max_x).contains(&lon) {
+                points.push(self.point(outer, lon));
+            }
+        }
+
+        points.iter().fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |[min_x, min_y, max_x, max_y], (x, y)| {
+                [min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)]
+            },
+        )
+    }
+}
+
+/// Normalize a longitude to the range `[-180..180)`.
+fn normalize_longitude(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// A planar projection for non-geographic data, such as scatterplots or image annotations.
+///
+/// Points in a user-supplied extent rectangle are scaled uniformly into the unit square, so that
+/// distances along both axes are comparable. The minimum corner of the extent is mapped to the
+/// top-left corner `(0, 0)` of the unit square. Points should lie within the extent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Planar {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl Planar {
+    /// Construct a new planar projection for points within the given extent.
+    ///
+    /// The extent must be finite and non-empty, otherwise
+    /// [`SuperclusterBuilder::try_finish`][crate::SuperclusterBuilder::try_finish] returns
+    /// [`SuperclusterError::InvalidBounds`][crate::error::SuperclusterError::InvalidBounds].
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Self {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// The length of the longer side of the extent, which is mapped to the unit square.
+    fn scale(&self) -> f64 {
+        (self.max_x - self.min_x).max(self.max_y - self.min_y)
+    }
+}
+
+impl Projection for Planar {
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        let scale = self.scale();
+        ((x - self.min_x) / scale, (y - self.min_y) / scale)
+    }
+
+    fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let scale = self.scale();
+        (x * scale + self.min_x, y * scale + self.min_y)
+    }
+
+    fn bounds(&self) -> [f64; 4] {
+        [self.min_x, self.min_y, self.max_x, self.max_y]
+    }
+
+    fn tag(&self) -> u64 {
+        4
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        assert!((a.0 - b.0).abs() < 1e-9, "{:?} != {:?}", a, b);
+        assert!((a.1 - b.1).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_meters_roundtrip() {
+        let projection = WebMercator::new(MercatorUnits::Meters);
+        assert_eq!(projection.project(0.0, 0.0), (0.5, 0.5));
+        assert_eq!(
+            projection.project(-HALF_CIRCUMFERENCE, HALF_CIRCUMFERENCE),
+            (0.0, 0.0)
+        );
+        assert_eq!(
+            projection.unproject(1.0, 1.0),
+            (HALF_CIRCUMFERENCE, -HALF_CIRCUMFERENCE)
+        );
+    }
+
+    #[test]
+    fn test_meters_match_degrees() {
+        let (x, y) = WebMercator::default().project(-79.04, 43.08);
+        let meters = WebMercator::new(MercatorUnits::Meters);
+        let (mx, my) = meters.unproject(x, y);
+        assert_close(meters.project(mx, my), (x, y));
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let projection = Equirectangular;
+        assert_eq!(projection.project(0.0, 0.0), (0.5, 0.5));
+        assert_eq!(projection.project(-180.0, 90.0), (0.0, 0.25));
+        assert_close(projection.unproject(0.75, 0.375), (90.0, 45.0));
+    }
+
+    #[test]
+    fn test_polar_stereographic() {
+        let north = PolarStereographic::north();
+        assert_eq!(north.project(0.0, 90.0), (0.5, 0.5));
+        assert_close(north.project(0.0, 0.0), (0.5, 1.0));
+        assert_close(north.project(90.0, 0.0), (1.0, 0.5));
+        let (x, y) = north.project(-120.0, 75.0);
+        assert_close(north.unproject(x, y), (-120.0, 75.0));
+
+        let south = PolarStereographic::south();
+        assert_eq!(south.project(0.0, -90.0), (0.5, 0.5));
+        assert_close(south.project(0.0, 0.0), (0.5, 0.0));
+        let (x, y) = south.project(45.0, -70.0);
+        assert_close(south.unproject(x, y), (45.0, -70.0));
+    }
+
+    #[test]
+    fn test_polar_stereographic_antimeridian() {
+        let north = PolarStereographic::north();
+        assert_close(north.project(190.0, 75.0), north.project(-170.0, 75.0));
+
+        // A bounding box across the antimeridian contains the points on both sides
+        let [min_x, min_y, max_x, max_y] = north.project_bbox(170.0, 60.0, 190.0, 80.0);
+        for lon in [170.0, 180.0, -170.0] {
+            let (x, y) = north.project(lon, 70.0);
+            assert!(x >= min_x - 1e-12 && x <= max_x + 1e-12);
+            assert!(y >= min_y - 1e-12 && y <= max_y + 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_planar() {
+        let projection = Planar::new(100.0, -50.0, 300.0, 50.0);
+        assert_eq!(projection.project(100.0, -50.0), (0.0, 0.0));
+        assert_eq!(projection.project(300.0, 50.0), (1.0, 0.5));
+        assert_eq!(projection.unproject(0.5, 0.25), (200.0, 0.0));
+    }
+
+    #[test]
+    fn test_polar_stereographic_bbox() {
+        let north = PolarStereographic::north();
+        let [min_x, min_y, max_x, max_y] = north.project_bbox(-10.0, 60.0, 100.0, 80.0);
+        for lon in [-10.0, 0.0, 45.0, 90.0, 100.0] {
+            for lat in [60.0, 70.0, 80.0] {
+                let (x, y) = north.project(lon, lat);
+                assert!(x >= min_x - 1e-12 && x <= max_x + 1e-12);
+                assert!(y >= min_y - 1e-12 && y <= max_y + 1e-12);
+            }
+        }
+    }
+}
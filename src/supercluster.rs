@@ -1,100 +1,129 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use geo_index::kdtree::KDTreeIndex;
-
+use crate::builder::CoordinatePolicy;
 use crate::cluster::{ClusterId, ClusterInfo};
 use crate::error::SuperclusterError;
+use crate::hierarchy::HierarchyEntry;
+use crate::index::ClusterIndex;
 use crate::options::SuperclusterOptions;
+use crate::projection::Projection;
+use crate::r#type::ClusterFloat;
+use crate::tile::TileFeature;
 use crate::tree::TreeWithData;
-use crate::util::{latitude_to_y, longitude_to_x};
 
 /// Create this via a [SuperclusterBuilder][crate::SuperclusterBuilder].
+///
+/// `N` is the floating point type used to store projected coordinates and `P` is the type of
+/// properties accumulated on each cluster by a [ClusterAggregator][crate::ClusterAggregator].
 #[derive(Debug, Clone)]
-pub struct Supercluster {
-    options: SuperclusterOptions,
+pub struct Supercluster<N: ClusterFloat = f64, P = ()> {
+    pub(crate) options: SuperclusterOptions,
 
     /// Vector of KDBush structures for different zoom levels
-    trees: HashMap<usize, TreeWithData>,
+    pub(crate) trees: HashMap<usize, TreeWithData<N, P>>,
 
     /// Note: these points are in the user's original coordinate system (usually lon-lat).
-    points: Vec<(f64, f64)>,
+    pub(crate) points: Vec<(f64, f64)>,
+
+    /// The id of each point, in ascending order.
+    pub(crate) ids: Vec<u64>,
+
+    /// The id each cluster is stored with in `trees`, by its public id.
+    pub(crate) stored_ids: HashMap<ClusterId, ClusterId>,
+
+    /// The projection from the user's original coordinate system to the unit square.
+    pub(crate) projection: Arc<dyn Projection>,
+
+    /// How points with invalid coordinates are handled, including inserted points.
+    pub(crate) coordinate_policy: CoordinatePolicy,
+
+    /// The ids of points that were left out of the index by `CoordinatePolicy::Skip`.
+    pub(crate) skipped_points: Vec<u64>,
+
+    /// The ids of points that were moved within bounds by `CoordinatePolicy::Clamp`.
+    pub(crate) clamped_points: Vec<u64>,
 }
 
-impl Supercluster {
+impl<N: ClusterFloat, P: Clone> Supercluster<N, P> {
     pub(crate) fn new(
         points: Vec<(f64, f64)>,
-        trees: HashMap<usize, TreeWithData>,
+        ids: Vec<u64>,
+        trees: HashMap<usize, TreeWithData<N, P>>,
         options: SuperclusterOptions,
+        projection: Arc<dyn Projection>,
     ) -> Self {
         Self {
             options,
             trees,
             points,
+            ids,
+            stored_ids: HashMap::new(),
+            projection,
+            coordinate_policy: CoordinatePolicy::default(),
+            skipped_points: vec![],
+            clamped_points: vec![],
         }
     }
 
+    /// Build the lookup of clusters by their public id, after the trees were built.
+    pub(crate) fn index_cluster_ids(&mut self) {
+        self.stored_ids = self.cluster_ids().into_iter().collect();
+    }
+
+    /// The ids of points that were left out of the index because they had non-finite or out of
+    /// range coordinates, with [`CoordinatePolicy::Skip`][crate::CoordinatePolicy::Skip],
+    /// including inserted points.
+    pub fn skipped_points(&self) -> &[u64] {
+        &self.skipped_points
+    }
+
+    /// The ids of points whose out of range coordinates were moved within the bounds of the
+    /// projection, with [`CoordinatePolicy::Clamp`][crate::CoordinatePolicy::Clamp]. Their leaves
+    /// have the clamped coordinates.
+    pub fn clamped_points(&self) -> &[u64] {
+        &self.clamped_points
+    }
+
     /// Get clusters within a given bounding box and zoom.
     ///
+    /// The bounding box is in the same units as the points added to the
+    /// [SuperclusterBuilder][crate::SuperclusterBuilder], usually longitude and latitude. Bounding
+    /// boxes crossing the antimeridian are supported.
+    ///
     /// Returns a vec of [ClusterInfo] objects which point into indices of the original input data.
     pub fn get_clusters(
         &self,
-        min_lng: f64,
-        min_lat: f64,
-        max_lng: f64,
-        max_lat: f64,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
         zoom: usize,
-    ) -> Vec<ClusterInfo> {
-        let mut min_lng = ((min_lng + 180.0) % 360.0 + 360.0) % 360.0 - 180.0;
-        let min_lat = min_lat.clamp(-90.0, 90.0);
-        let mut max_lng = if max_lng == 180.0 {
-            180.0
-        } else {
-            ((max_lng + 180.0) % 360.0 + 360.0) % 360.0 - 180.0
-        };
-        let max_lat = max_lat.clamp(-90.0, 90.0);
-
-        if max_lng - min_lng >= 360.0 {
-            min_lng = -180.0;
-            max_lng = 180.0;
-        } else if min_lng > max_lng {
-            let mut eastern_hem = self.get_clusters(min_lng, min_lat, 180.0, max_lat, zoom);
-            let mut western_hem = self.get_clusters(-180.0, min_lat, max_lng, max_lat, zoom);
-            eastern_hem.append(&mut western_hem);
-            return eastern_hem;
-        }
-
-        let tree_with_data = self.trees.get(&self.clamp_zoom(zoom)).unwrap();
-
-        // NOTE! it is intentional for max_lat to be passed to min_y and for min_lat to be passed
-        // to max_y. Apparently the spherical mercator coord system has a flipped y.
-        let ids = tree_with_data.tree.as_ref().range(
-            longitude_to_x(min_lng),
-            latitude_to_y(max_lat),
-            longitude_to_x(max_lng),
-            latitude_to_y(min_lat),
-        );
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters(min_x, min_y, max_x, max_y, zoom)
+    }
 
-        let data = tree_with_data.data();
-
-        let mut clusters = Vec::with_capacity(ids.len());
-        for id in ids {
-            let cluster_data = &data[id];
-
-            // If there's more than one point in this cluster, group them.
-            if cluster_data.num_points > 1 {
-                clusters.push(ClusterInfo::new_cluster(
-                    cluster_data.source_id,
-                    cluster_data.x,
-                    cluster_data.y,
-                    cluster_data.num_points,
-                ));
-            } else {
-                let (x, y) = self.points[id];
-                clusters.push(ClusterInfo::new_leaf(cluster_data.source_id, x, y))
-            }
-        }
+    /// Returns the clusters and points inside a polygon at the given zoom.
+    ///
+    /// The polygon is a ring of vertices in the units of the input points, which may or may not
+    /// repeat the first vertex at the end. Its bounding box is used to query the index, and the
+    /// results are then filtered exactly with the even-odd rule. If the
+    /// [projection][crate::Projection::wraps] wraps around the antimeridian, an edge between two
+    /// vertices more than half the world apart is taken to cross the antimeridian, so for example
+    /// a ring with longitudes `170` and `-170` covers 20 degrees rather than 340.
+    pub fn get_clusters_in_polygon(&self, ring: &[(f64, f64)], zoom: usize) -> Vec<ClusterInfo<P>> {
+        self.clusters_in_polygon(ring, &[], zoom)
+    }
 
-        clusters
+    /// Returns the clusters and points inside a [`geo_types::Polygon`] at the given zoom,
+    /// excluding its holes. See [`get_clusters_in_polygon`][Self::get_clusters_in_polygon].
+    #[cfg(feature = "geo-types")]
+    pub fn get_clusters_in_geo_polygon(
+        &self,
+        polygon: &geo_types::Polygon<f64>,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters_in_geo_polygon(polygon, zoom)
     }
 
     /// Returns the children of a cluster (on the next zoom level) given its id.
@@ -103,54 +132,8 @@ impl Supercluster {
     pub fn get_children(
         &self,
         cluster_id: ClusterId,
-    ) -> Result<Vec<ClusterInfo>, SuperclusterError> {
-        let origin_id = self.get_origin_idx(cluster_id);
-        let origin_zoom = self.get_origin_zoom(cluster_id);
-
-        let tree_with_data = match self.trees.get(&origin_zoom) {
-            Some(tree_with_data) => tree_with_data,
-            None => return Err(SuperclusterError::NoClusterFound),
-        };
-
-        let data = tree_with_data.data();
-        let tree = tree_with_data.tree();
-        if origin_id >= data.len() {
-            return Err(SuperclusterError::NoClusterFound);
-        }
-
-        let r = self.options.radius
-            / (self.options.extent * usize::pow(2, (origin_zoom - 1).try_into().unwrap()) as f64);
-        let x = data[origin_id].x;
-        let y = data[origin_id].y;
-        let ids = tree.as_ref().within(x, y, r);
-        let mut children = vec![];
-
-        for id in ids {
-            let cluster_data = &data[id];
-
-            if cluster_data
-                .parent_id
-                .is_some_and(|parent_id| parent_id == cluster_id)
-            {
-                if cluster_data.num_points > 1 {
-                    children.push(ClusterInfo::new_cluster(
-                        cluster_data.source_id,
-                        cluster_data.x,
-                        cluster_data.y,
-                        cluster_data.num_points,
-                    ));
-                } else {
-                    let (x, y) = self.points[id];
-                    children.push(ClusterInfo::new_leaf(cluster_data.source_id, x, y))
-                }
-            }
-        }
-
-        if children.is_empty() {
-            return Err(SuperclusterError::NoClusterFound);
-        }
-
-        Ok(children)
+    ) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.children(cluster_id)
     }
 
     /// Returns all the points of a cluster (given its cluster_id), with pagination support: limit
@@ -165,25 +148,117 @@ impl Supercluster {
         cluster_id: ClusterId,
         limit: Option<usize>,
         offset: Option<usize>,
-    ) -> Result<Vec<ClusterInfo>, SuperclusterError> {
-        let limit = limit.unwrap_or(10);
-        let offset = offset.unwrap_or(0);
+    ) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.leaves(cluster_id, limit, offset)
+    }
 
-        let mut leaves = vec![];
-        self.append_leaves(&mut leaves, cluster_id, limit, offset, 0)?;
+    /// Returns the features within a given XYZ tile.
+    ///
+    /// Feature coordinates are integer positions relative to the tile, in the range
+    /// `0..options.extent`. Features within `options.radius` pixels of the tile's edges are also
+    /// included, so that clusters are not clipped when rendering. If the
+    /// [projection][crate::Projection::wraps] wraps around the antimeridian, tiles on the
+    /// antimeridian include features wrapped from the other side of the world.
+    pub fn get_tile(&self, z: usize, x: usize, y: usize) -> Vec<TileFeature<P>> {
+        self.tile(z, x, y)
+    }
 
-        Ok(leaves)
+    /// Returns the bounding box of the points of a cluster given its id, as
+    /// `[min_x, min_y, max_x, max_y]` in the units of the input points.
+    ///
+    /// This is useful to fit a map view to a cluster without fetching its leaves. The bounds of
+    /// clusters returned by queries are also available via [`ClusterInfo::bounds`].
+    pub fn get_cluster_bounds(&self, cluster_id: ClusterId) -> Result<[f64; 4], SuperclusterError> {
+        self.cluster_bounds(cluster_id)
     }
 
-    // pub fn get_tile(self, z: usize, x: usize, y: usize) {
-    //     let tree = self.trees.get(&self.clamp_zoom(z)).unwrap();
-    //     let z2 = usize::pow(2, z.try_into().unwrap()) as f64;
-    //     let p = self.options.radius / self.options.extent;
-    //     let top = (y as f64 - p) / z2;
-    //     let bottom = (y as f64 + 1.0 + p) / z2;
+    /// Returns the convex hull of the points of a cluster given its id, in the units of the input
+    /// points.
+    ///
+    /// The vertices are in counter-clockwise order, and the first vertex is not repeated at the
+    /// end. The hull of coincident or collinear points has one or two vertices. If x is periodic,
+    /// as longitude is, vertices are unwrapped relative to the cluster, so the hull of a cluster
+    /// crossing the antimeridian may extend past 180 or -180.
+    ///
+    /// The hull is merged from the hulls of the cluster's children on each call, so it is best
+    /// suited to a single cluster, for example on hover.
+    pub fn get_cluster_hull(
+        &self,
+        cluster_id: ClusterId,
+    ) -> Result<Vec<(f64, f64)>, SuperclusterError> {
+        self.cluster_hull(cluster_id)
+    }
 
-    //     todo!()
-    // }
+    /// Returns the cluster containing an input point at the given zoom, given the id of the
+    /// point, or the point itself if it is not clustered at that zoom.
+    ///
+    /// The zoom is clamped like in [`get_clusters`][Self::get_clusters]. This is useful to
+    /// highlight the cluster containing a selected point, for example after a search.
+    pub fn get_cluster_for_point(
+        &self,
+        point_id: u64,
+        zoom: usize,
+    ) -> Result<ClusterInfo<P>, SuperclusterError> {
+        self.point_cluster(point_id, zoom)
+    }
+
+    /// Returns the clusters containing an input point given its id, from the smallest cluster,
+    /// formed at the highest zoom, to the largest, formed at `min_zoom`.
+    ///
+    /// Each cluster is listed once, even if it is unchanged over several zooms. The list is empty
+    /// if the point is not clustered at any zoom.
+    pub fn get_ancestors(&self, point_id: u64) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.ancestors(point_id)
+    }
+
+    /// Returns every point and cluster at every zoom, from `min_zoom` to `max_zoom + 1`, with
+    /// the id of the cluster it is merged into at the next lower zoom.
+    ///
+    /// This walks each zoom once in index order, so the whole hierarchy can be exported, for
+    /// example to a database, without calling [`get_children`][Self::get_children] recursively.
+    /// Removed points are left out.
+    pub fn hierarchy(&self) -> impl Iterator<Item = HierarchyEntry> + '_ {
+        ClusterIndex::hierarchy(self)
+    }
+
+    /// Returns the point or cluster nearest to `(x, y)` at the given zoom, if any is within
+    /// `max_pixel_distance`. The location is in the units of the input points.
+    ///
+    /// The distance is in pixels relative to the tile extent, like
+    /// [`radius`][SuperclusterOptions::radius], and is measured to the displayed position of
+    /// each point or cluster, as returned by [`get_clusters`][Self::get_clusters]. This is
+    /// useful for hit testing a click on the map.
+    pub fn nearest(
+        &self,
+        x: f64,
+        y: f64,
+        zoom: usize,
+        max_pixel_distance: f64,
+    ) -> Option<ClusterInfo<P>> {
+        ClusterIndex::nearest(self, x, y, zoom, max_pixel_distance)
+    }
+
+    /// Returns every point and cluster within `radius_pixels` of `(x, y)` at the given zoom, from
+    /// nearest to farthest. The location is in the units of the input points.
+    ///
+    /// The radius is in pixels relative to the tile extent, like
+    /// [`radius`][SuperclusterOptions::radius], and is measured to the displayed position of
+    /// each point or cluster. Unlike filtering the result of [`get_clusters`][Self::get_clusters],
+    /// this uses a radius query on the index.
+    ///
+    /// A radius in meters is out of scope: pixels are meaningful for every
+    /// [projection][crate::Projection], while the length of a pixel on the ground depends on the
+    /// projection and, for Web Mercator, on the latitude. Convert meters to pixels at the zoom of
+    /// the query before calling this.
+    pub fn get_clusters_within(
+        &self,
+        x: f64,
+        y: f64,
+        radius_pixels: f64,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters_within(x, y, radius_pixels, zoom)
+    }
 
     /// Returns the zoom on which the cluster expands into several children (useful for "click to
     /// zoom" feature) given the cluster's id.
@@ -193,78 +268,51 @@ impl Supercluster {
         &self,
         cluster_id: ClusterId,
     ) -> Result<usize, SuperclusterError> {
-        let mut cluster_id = cluster_id;
-        let mut expansion_zoom = cluster_id.get_origin_zoom(self.points.len()) - 1;
-        while expansion_zoom <= self.options.max_zoom {
-            let children = self.get_children(cluster_id)?;
-            expansion_zoom += 1;
-            if children.len() != 1 {
-                break;
-            }
-            cluster_id = children[0].id();
-        }
-
-        Ok(expansion_zoom)
+        self.expansion_zoom(cluster_id)
     }
+}
 
-    fn append_leaves(
-        &self,
-        result: &mut Vec<ClusterInfo>,
-        cluster_id: ClusterId,
-        limit: usize,
-        offset: usize,
-        skipped: usize,
-    ) -> Result<usize, SuperclusterError> {
-        let children = self.get_children(cluster_id)?;
+impl<N: ClusterFloat, P: Clone> ClusterIndex<N, P> for Supercluster<N, P> {
+    type Level = TreeWithData<N, P>;
 
-        let mut skipped = skipped;
+    fn options(&self) -> &SuperclusterOptions {
+        &self.options
+    }
 
-        for child in children {
-            if child.is_cluster() {
-                if skipped + child.count() <= offset {
-                    // skip the whole cluster
-                    skipped += child.count();
-                } else {
-                    // enter the cluster
-                    skipped = self.append_leaves(result, child.id(), limit, offset, skipped)?;
-                    // exit the cluster
-                }
-                skipped += 1;
-            } else if skipped < offset {
-                // skip a single point
-                skipped += 1;
-            } else {
-                // add a single point
-                result.push(child);
-            }
+    fn projection(&self) -> &dyn Projection {
+        self.projection.as_ref()
+    }
 
-            if result.len() == limit {
-                break;
-            }
-        }
+    fn point(&self, idx: usize) -> (f64, f64) {
+        self.points[idx]
+    }
 
-        Ok(skipped)
+    fn point_id(&self, idx: usize) -> u64 {
+        self.ids[idx]
     }
 
-    fn clamp_zoom(&self, zoom: usize) -> usize {
-        zoom.clamp(self.options.min_zoom, self.options.max_zoom + 1)
+    fn point_idx(&self, point_id: u64) -> Option<usize> {
+        self.ids.binary_search(&point_id).ok()
     }
 
-    /// get index of the point from which the cluster originated
-    fn get_origin_idx(&self, cluster_id: ClusterId) -> usize {
-        cluster_id.get_origin_idx(self.points.len())
+    fn zoom_level(&self, zoom: usize) -> Option<&Self::Level> {
+        self.trees.get(&zoom)
     }
 
-    /// get zoom of the point from which the cluster originated
-    fn get_origin_zoom(&self, cluster_id: ClusterId) -> usize {
-        cluster_id.get_origin_zoom(self.points.len())
+    fn stored_cluster_id(&self, cluster_id: ClusterId) -> Option<ClusterId> {
+        self.stored_ids.get(&cluster_id).copied()
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::error::SuperclusterError;
+    use crate::test::index::build_index;
     use crate::test::load_fixture::load_places;
-    use crate::SuperclusterBuilder;
+    use crate::{
+        ClusterId, ClusterInfo, MercatorUnits, PolarStereographic, Projection, SuperclusterBuilder,
+        WebMercator,
+    };
 
     #[test]
     fn test_builder() {
@@ -279,4 +327,516 @@ mod test {
         dbg!(&clusters);
         // dbg!(supercluster);
     }
+
+    /// Leaves must be resolved through their source id, not their position within a zoom.
+    #[test]
+    fn test_leaf_coordinates() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let check_leaf = |leaf: &ClusterInfo, zoom: usize| {
+            let coord = &coords[leaf.id().as_usize()];
+            assert_eq!((leaf.x(), leaf.y()), (coord[0], coord[1]), "zoom {zoom}");
+        };
+
+        let mut num_leaves = 0;
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in &clusters {
+                if !cluster.is_cluster() {
+                    check_leaf(cluster, zoom);
+                    num_leaves += 1;
+                    continue;
+                }
+
+                let children = supercluster.get_children(cluster.id()).unwrap();
+                for child in children.iter().filter(|child| !child.is_cluster()) {
+                    check_leaf(child, zoom);
+                    num_leaves += 1;
+                }
+
+                let leaves = supercluster
+                    .get_leaves(cluster.id(), Some(usize::MAX), None)
+                    .unwrap();
+                for leaf in &leaves {
+                    check_leaf(leaf, zoom);
+                }
+            }
+        }
+
+        // The test would pass trivially if there were no leaves at lower zooms
+        assert!(num_leaves > coords.len());
+    }
+
+    #[test]
+    fn test_cluster_hull() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        for zoom in [0, 2, 5] {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in clusters.iter().filter(|cluster| cluster.is_cluster()) {
+                let hull = supercluster.get_cluster_hull(cluster.id()).unwrap();
+                let leaves = supercluster
+                    .get_leaves(cluster.id(), Some(usize::MAX), None)
+                    .unwrap();
+
+                // Every vertex is a leaf, and every leaf is on the inner side of every edge
+                let leaves: Vec<(f64, f64)> = leaves
+                    .iter()
+                    .map(|leaf| {
+                        let x = leaf.x() - 360.0 * ((leaf.x() - cluster.x()) / 360.0).round();
+                        (x, leaf.y())
+                    })
+                    .collect();
+                for vertex in &hull {
+                    assert!(leaves.contains(vertex));
+                }
+                if hull.len() < 3 {
+                    continue;
+                }
+                for (i, a) in hull.iter().enumerate() {
+                    let b = hull[(i + 1) % hull.len()];
+                    for leaf in &leaves {
+                        let cross = (b.0 - a.0) * (leaf.1 - a.1) - (b.1 - a.1) * (leaf.0 - a.0);
+                        assert!(cross >= -1e-9, "zoom {zoom}");
+                    }
+                }
+            }
+        }
+
+        assert!(supercluster
+            .get_cluster_hull(ClusterId::new_source_id(0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_cluster_hull_antimeridian() {
+        let coords = vec![
+            vec![179.5, 80.0],
+            vec![179.8, 81.0],
+            vec![-179.5, 80.5],
+            vec![-179.8, 79.5],
+        ];
+        let mut builder =
+            SuperclusterBuilder::new(coords.len()).with_projection(PolarStereographic::north());
+        for coord in &coords {
+            builder.add(coord[0], coord[1]);
+        }
+        let supercluster = builder.finish();
+
+        // In polar stereographic, the antimeridian is not an edge of the projected plane, so a
+        // cluster may span it
+        let clusters = supercluster.get_clusters(-180.0, 0.0, 180.0, 90.0, 0);
+        assert_eq!(clusters.len(), 1);
+
+        let hull = supercluster.get_cluster_hull(clusters[0].id()).unwrap();
+        assert_eq!(hull.len(), 4);
+        let min_x = hull.iter().map(|(x, _)| *x).fold(f64::INFINITY, f64::min);
+        let max_x = hull
+            .iter()
+            .map(|(x, _)| *x)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert!((max_x - min_x - 1.0).abs() < 1e-9, "{hull:?}");
+    }
+
+    #[test]
+    fn test_invalid_cluster_id() {
+        let supercluster = build_index(&load_places());
+
+        // A leaf, and an id with the cluster bit set but an origin zoom of 0
+        for id in [ClusterId::new_source_id(0), ClusterId::from(1 << 63)] {
+            assert!(matches!(
+                supercluster.get_children(id),
+                Err(SuperclusterError::NoClusterFound)
+            ));
+            assert!(matches!(
+                supercluster.get_cluster_expansion_zoom(id),
+                Err(SuperclusterError::NoClusterFound)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_cluster_for_point() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let contains = |cluster: &ClusterInfo, point_id: u64| {
+            supercluster
+                .get_leaves(cluster.id(), Some(usize::MAX), None)
+                .unwrap()
+                .iter()
+                .any(|leaf| leaf.id().as_u64() == point_id)
+        };
+
+        for point_id in (0..coords.len() as u64).step_by(17) {
+            for zoom in 0..=17 {
+                let cluster = supercluster.get_cluster_for_point(point_id, zoom).unwrap();
+                if cluster.is_cluster() {
+                    assert!(contains(&cluster, point_id), "zoom {zoom}");
+                } else {
+                    assert_eq!(cluster.id().as_u64(), point_id, "zoom {zoom}");
+                }
+            }
+
+            // Once clustered, a point stays clustered at lower zooms
+            let ancestors = supercluster.get_ancestors(point_id).unwrap();
+            let top = supercluster.get_cluster_for_point(point_id, 0).unwrap();
+            assert_eq!(
+                ancestors.last().map(|cluster| cluster.id()),
+                top.is_cluster().then(|| top.id())
+            );
+            for pair in ancestors.windows(2) {
+                assert!(pair[0].count() < pair[1].count());
+            }
+            for ancestor in &ancestors {
+                assert!(contains(ancestor, point_id));
+            }
+        }
+
+        assert!(supercluster
+            .get_cluster_for_point(coords.len() as u64, 0)
+            .is_err());
+        assert!(supercluster.get_ancestors(coords.len() as u64).is_err());
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let entries: Vec<_> = supercluster.hierarchy().collect();
+        for zoom in 0..=17 {
+            let mut ids: Vec<_> = entries
+                .iter()
+                .filter(|entry| entry.zoom() == zoom)
+                .map(|entry| (entry.id(), entry.count()))
+                .collect();
+            ids.sort();
+            let mut expected: Vec<_> = supercluster
+                .get_clusters(-180.0, -90.0, 180.0, 90.0, zoom)
+                .iter()
+                .map(|cluster| (cluster.id(), cluster.count()))
+                .collect();
+            expected.sort();
+            assert_eq!(ids, expected, "zoom {zoom}");
+        }
+
+        for entry in &entries {
+            let Some(parent_id) = entry.parent_id() else {
+                continue;
+            };
+            assert!(entries
+                .iter()
+                .any(|parent| parent.zoom() == entry.zoom() - 1 && parent.id() == parent_id));
+            let children = supercluster.get_children(parent_id).unwrap();
+            assert!(children.iter().any(|child| child.id() == entry.id()));
+        }
+    }
+
+    /// The distance between two locations in pixels at `zoom`, relative to the default extent,
+    /// wrapping around the antimeridian.
+    fn pixels(a: (f64, f64), b: (f64, f64), zoom: usize) -> f64 {
+        let projection = WebMercator::default();
+        let (ax, ay) = projection.project(a.0, a.1);
+        let (bx, by) = projection.project(b.0, b.1);
+        let dx = (ax - bx).abs().min(1.0 - (ax - bx).abs());
+        let scale = 512.0 * f64::powi(2.0, zoom as i32);
+        (dx.powi(2) + (ay - by).powi(2)).sqrt() * scale
+    }
+
+    #[test]
+    fn test_nearest() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let mut num_found = 0;
+        for zoom in [0, 3, 8] {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for query in coords.iter().step_by(11) {
+                let query = (query[0] + 1.0, query[1] - 0.5);
+                let expected = clusters
+                    .iter()
+                    .map(|cluster| (pixels(query, (cluster.x(), cluster.y()), zoom), cluster))
+                    .filter(|(distance, _)| *distance <= 30.0)
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .map(|(_, cluster)| cluster.id());
+                let nearest = supercluster.nearest(query.0, query.1, zoom, 30.0);
+                assert_eq!(nearest.map(|cluster| cluster.id()), expected, "zoom {zoom}");
+                num_found += usize::from(expected.is_some());
+            }
+        }
+        assert!(num_found > 0);
+
+        let cluster = &supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 2)[0];
+        let nearest = supercluster
+            .nearest(cluster.x(), cluster.y(), 2, 0.5)
+            .unwrap();
+        assert_eq!(nearest.id(), cluster.id());
+
+        // Points just across the antimeridian are found
+        let mut builder = SuperclusterBuilder::new(1);
+        builder.add(179.9, 0.0);
+        let supercluster = builder.finish();
+        assert!(supercluster.nearest(-179.9, 0.0, 5, 10.0).is_some());
+        assert!(supercluster.nearest(-179.9, 0.0, 5, 1.0).is_none());
+
+        // Zooms past the full resolution zoom measure pixels at that zoom
+        let max_zoom = supercluster.options.max_zoom;
+        let nearest = supercluster.nearest(179.90001, 0.0, max_zoom + 1, 10.0);
+        assert!(nearest.is_some());
+        assert_eq!(
+            supercluster
+                .nearest(179.90001, 0.0, 30, 10.0)
+                .map(|leaf| leaf.id()),
+            nearest.map(|leaf| leaf.id())
+        );
+    }
+
+    #[test]
+    fn test_get_clusters_within() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        for zoom in [0, 2, 4] {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for query in [(-79.0, 43.0), (10.0, 50.0), (179.5, -17.0)] {
+                let mut expected: Vec<_> = clusters
+                    .iter()
+                    .filter(|cluster| pixels(query, (cluster.x(), cluster.y()), zoom) <= 100.0)
+                    .map(|cluster| cluster.id())
+                    .collect();
+                expected.sort();
+
+                let within = supercluster.get_clusters_within(query.0, query.1, 100.0, zoom);
+                let distances: Vec<f64> = within
+                    .iter()
+                    .map(|cluster| pixels(query, (cluster.x(), cluster.y()), zoom))
+                    .collect();
+                assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+
+                let mut ids: Vec<_> = within.iter().map(|cluster| cluster.id()).collect();
+                ids.sort();
+                assert_eq!(ids, expected, "zoom {zoom}");
+            }
+        }
+
+        // Every item is found once, even if the radius spans the antimeridian
+        let all = supercluster.get_clusters_within(0.0, 0.0, 1000.0, 0);
+        let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 0);
+        assert_eq!(all.len(), clusters.len());
+    }
+
+    #[test]
+    fn test_get_clusters_in_polygon() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let ids = |clusters: Vec<ClusterInfo>| -> Vec<ClusterId> {
+            let mut ids: Vec<_> = clusters.iter().map(|cluster| cluster.id()).collect();
+            ids.sort();
+            ids
+        };
+
+        for zoom in [0, 2, 5] {
+            // A rectangle matches a bounding box query
+            let ring = [(-100.0, 0.0), (-40.0, 0.0), (-40.0, 60.0), (-100.0, 60.0)];
+            let expected = supercluster.get_clusters(-100.0, 0.0, -40.0, 60.0, zoom);
+            assert!(!expected.is_empty());
+            assert_eq!(
+                ids(supercluster.get_clusters_in_polygon(&ring, zoom)),
+                ids(expected)
+            );
+
+            // A triangle keeps the half of the rectangle above its diagonal
+            let ring = [(-100.0, 0.0), (-40.0, 60.0), (-100.0, 60.0), (-100.0, 0.0)];
+            let expected: Vec<_> = supercluster
+                .get_clusters(-100.0, 0.0, -40.0, 60.0, zoom)
+                .into_iter()
+                .filter(|cluster| cluster.y() > cluster.x() + 100.0)
+                .collect();
+            assert_eq!(
+                ids(supercluster.get_clusters_in_polygon(&ring, zoom)),
+                ids(expected)
+            );
+
+            // A rectangle across the antimeridian
+            let ring = [(150.0, -50.0), (-150.0, -50.0), (-150.0, 0.0), (150.0, 0.0)];
+            let expected = supercluster.get_clusters(150.0, -50.0, -150.0, 0.0, zoom);
+            assert!(!expected.is_empty());
+            assert_eq!(
+                ids(supercluster.get_clusters_in_polygon(&ring, zoom)),
+                ids(expected)
+            );
+        }
+
+        assert!(supercluster
+            .get_clusters_in_polygon(&[(0.0, 0.0), (10.0, 10.0)], 0)
+            .is_empty());
+    }
+
+    #[cfg(feature = "geo-types")]
+    #[test]
+    fn test_get_clusters_in_geo_polygon() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        // Edges wider than half the world would cross the antimeridian
+        let exterior = vec![
+            (-180.0, -85.0),
+            (0.0, -85.0),
+            (180.0, -85.0),
+            (180.0, 85.0),
+            (0.0, 85.0),
+            (-180.0, 85.0),
+        ];
+        let hole = vec![(-100.0, 0.0), (-40.0, 0.0), (-40.0, 60.0), (-100.0, 60.0)];
+        let polygon = geo_types::Polygon::new(exterior.into(), vec![hole.into()]);
+
+        let clusters = supercluster.get_clusters_in_geo_polygon(&polygon, 3);
+        let in_hole = supercluster.get_clusters(-100.0, 0.0, -40.0, 60.0, 3);
+        assert!(!in_hole.is_empty());
+        assert!(clusters
+            .iter()
+            .all(|cluster| in_hole.iter().all(|other| other.id() != cluster.id())));
+        assert_eq!(
+            clusters.len() + in_hole.len(),
+            supercluster
+                .get_clusters(-180.0, -85.0, 180.0, 85.0, 3)
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_get_tile() {
+        let coords = load_places();
+        let supercluster = build_index(&coords);
+
+        let tile = supercluster.get_tile(0, 0, 0);
+        assert_eq!(tile.len(), 39);
+        for feature in &tile {
+            assert!((-40..=552).contains(&feature.x()));
+            assert!((-40..=552).contains(&feature.y()));
+        }
+
+        // Points near longitude 180 are wrapped into the buffer of the westernmost tile
+        let west = supercluster.get_tile(1, 0, 0);
+        assert!(west.iter().any(|feature| feature.x() < 0));
+    }
+
+    #[test]
+    fn test_preprojected() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        let mut meters_builder =
+            SuperclusterBuilder::new(coords.len()).with_units(MercatorUnits::Meters);
+        for coord in coords {
+            builder.add(coord[0], coord[1]);
+            let (x, y) = WebMercator::default().project(coord[0], coord[1]);
+            let (x, y) = WebMercator::new(MercatorUnits::Meters).unproject(x, y);
+            meters_builder.add(x, y);
+        }
+        let supercluster = builder.finish();
+        let meters_supercluster = meters_builder.finish();
+
+        let clusters = supercluster.get_clusters(-180.0, -85.0, 180.0, 85.0, 2);
+        let [min_x, min_y, max_x, max_y] = WebMercator::new(MercatorUnits::Meters).bounds();
+        let meters_clusters = meters_supercluster.get_clusters(min_x, min_y, max_x, max_y, 2);
+        assert_eq!(clusters.len(), meters_clusters.len());
+
+        for (cluster, meters_cluster) in clusters.iter().zip(meters_clusters.iter()) {
+            assert_eq!(cluster.id(), meters_cluster.id());
+            assert_eq!(cluster.count(), meters_cluster.count());
+            let (x, y) = WebMercator::default().project(cluster.x(), cluster.y());
+            let (x, y) = WebMercator::new(MercatorUnits::Meters).unproject(x, y);
+            assert!((x - meters_cluster.x()).abs() < 1e-3);
+            assert!((y - meters_cluster.y()).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_polar_stereographic() {
+        let coords: Vec<_> = load_places()
+            .into_iter()
+            .filter(|coord| coord[1] > 0.0)
+            .collect();
+        let mut builder =
+            SuperclusterBuilder::new(coords.len()).with_projection(PolarStereographic::north());
+        for coord in &coords {
+            builder.add(coord[0], coord[1]);
+        }
+        let supercluster = builder.finish();
+
+        let clusters = supercluster.get_clusters(-180.0, 0.0, 180.0, 90.0, 0);
+        let total: usize = clusters.iter().map(|cluster| cluster.count()).sum();
+        assert_eq!(total, coords.len());
+
+        let leaves = supercluster.get_clusters(0.0, 40.0, 90.0, 90.0, 17);
+        let expected = coords
+            .iter()
+            .filter(|coord| (0.0..=90.0).contains(&coord[0]) && coord[1] >= 40.0)
+            .count();
+        assert_eq!(leaves.len(), expected);
+
+        // Longitude is periodic, so a bounding box may cross the antimeridian
+        let leaves = supercluster.get_clusters(90.0, 40.0, 0.0, 90.0, 17);
+        let expected = coords
+            .iter()
+            .filter(|coord| (coord[0] >= 90.0 || coord[0] <= 0.0) && coord[1] >= 40.0)
+            .count();
+        assert_eq!(leaves.len(), expected);
+
+        // and out of range longitudes are wrapped rather than clamped
+        let mut builder = SuperclusterBuilder::new(1).with_projection(PolarStereographic::north());
+        builder.add(200.0, 80.0);
+        let supercluster = builder.finish();
+        assert_eq!(supercluster.clamped_points(), &[0]);
+        let leaves = supercluster.get_clusters(-180.0, 0.0, 180.0, 90.0, 17);
+        assert_eq!((leaves[0].x(), leaves[0].y()), (-160.0, 80.0));
+    }
+
+    #[test]
+    fn test_planar() {
+        let mut builder = SuperclusterBuilder::new(200).with_planar_extent(0.0, 0.0, 1000.0, 500.0);
+        for i in 0..100 {
+            builder.add(10.0 + i as f64 * 0.01, 20.0);
+            builder.add(990.0, 480.0 - i as f64 * 0.01);
+        }
+        let supercluster = builder.finish();
+
+        let clusters = supercluster.get_clusters(0.0, 0.0, 1000.0, 500.0, 0);
+        assert_eq!(clusters.len(), 2);
+        let left = clusters.iter().find(|cluster| cluster.x() < 500.0).unwrap();
+        assert_eq!(left.count(), 100);
+        assert!((left.x() - 10.495).abs() < 1e-6);
+        assert!((left.y() - 20.0).abs() < 1e-6);
+
+        let clusters = supercluster.get_clusters(900.0, 400.0, 1000.0, 500.0, 0);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].count(), 100);
+        assert!((clusters[0].x() - 990.0).abs() < 1e-6);
+
+        // Bounds are not wrapped around
+        assert!(supercluster
+            .get_clusters(900.0, 0.0, 100.0, 500.0, 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_planar_reversed_bbox() {
+        let mut builder = SuperclusterBuilder::new(1).with_planar_extent(0.0, 0.0, 1000.0, 1000.0);
+        builder.add(500.0, 500.0);
+        let supercluster = builder.finish();
+
+        assert_eq!(
+            supercluster
+                .get_clusters(100.0, 0.0, 900.0, 1000.0, 20)
+                .len(),
+            1
+        );
+        assert!(supercluster
+            .get_clusters(900.0, 0.0, 100.0, 1000.0, 20)
+            .is_empty());
+    }
 }
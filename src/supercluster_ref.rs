@@ -0,0 +1,715 @@
+use std::mem::size_of;
+use std::ops::Range;
+use std::sync::Arc;
+
+use bytemuck::Pod;
+use geo_index::kdtree::{KDTreeIndex, KDTreeRef};
+
+use crate::cluster::{ClusterId, ClusterInfo};
+use crate::error::SuperclusterError;
+use crate::format::{check_cluster_ids, check_ids, check_levels, LevelBytes, Reader};
+use crate::hierarchy::HierarchyEntry;
+use crate::index::{ClusterIndex, ClusterLevel};
+use crate::options::SuperclusterOptions;
+use crate::projection::{Projection, WebMercator};
+use crate::r#type::ClusterFloat;
+use crate::tile::TileFeature;
+
+/// A read-only view of an index serialized with
+/// [`Supercluster::to_bytes`][crate::Supercluster::to_bytes].
+///
+/// Queries are answered directly from the borrowed buffer, without copying or clustering, so a
+/// large index (for example, a memory-mapped file) opens with a single pass over its ids to
+/// validate them, or in constant time per zoom level with
+/// [`try_new_trusted`][SuperclusterRef::try_new_trusted]. The buffer must be aligned to 8 bytes, which is always the case for
+/// memory-mapped files.
+#[derive(Debug, Clone)]
+pub struct SuperclusterRef<'a, N: ClusterFloat = f64, P: Pod = ()> {
+    options: SuperclusterOptions,
+
+    /// Note: these points are in the user's original coordinate system (usually lon-lat).
+    points: &'a [[f64; 2]],
+
+    /// The id of each input point, in ascending order.
+    ids: &'a [u64],
+
+    /// The clusters at each zoom, starting at `min_zoom`.
+    levels: Vec<LevelRef<'a, N, P>>,
+
+    /// The public id of each cluster and the id it is stored with in `levels`, in ascending order
+    /// of public id.
+    stored_ids: &'a [[u64; 2]],
+
+    /// The ids of points that were left out of the index by `CoordinatePolicy::Skip`.
+    skipped_points: &'a [u64],
+
+    /// The ids of points that were moved within bounds by `CoordinatePolicy::Clamp`.
+    clamped_points: &'a [u64],
+
+    /// The projection from the user's original coordinate system to the unit square.
+    projection: Arc<dyn Projection>,
+}
+
+impl<'a, N: ClusterFloat, P: Pod> SuperclusterRef<'a, N, P> {
+    /// Open a serialized index whose points are projected with the default [`WebMercator`]
+    /// projection.
+    ///
+    /// The coordinate type `N` and property type `P` must match those of the serialized index. An
+    /// index built with another projection must be opened with
+    /// [`try_new_with_projection`][Self::try_new_with_projection].
+    pub fn try_new(bytes: &'a [u8]) -> Result<Self, SuperclusterError> {
+        Self::try_new_with_projection(bytes, WebMercator::default())
+    }
+
+    /// Open a serialized index whose points are projected with the given projection.
+    ///
+    /// The projection must be the one the index was built with, otherwise this returns
+    /// [`SuperclusterError::ProjectionMismatch`].
+    pub fn try_new_with_projection(
+        bytes: &'a [u8],
+        projection: impl Projection + 'static,
+    ) -> Result<Self, SuperclusterError> {
+        Self::open(bytes, projection, true)
+    }
+
+    /// Open a serialized index from a trusted source, such as a buffer this process wrote
+    /// itself.
+    ///
+    /// Unlike [`try_new`][Self::try_new], this only checks the header and the section
+    /// sizes, so opening takes constant time per zoom level instead of a pass over every
+    /// stored id. A corrupted buffer may cause panics or wrong results at query time.
+    pub fn try_new_trusted(bytes: &'a [u8]) -> Result<Self, SuperclusterError> {
+        Self::try_new_trusted_with_projection(bytes, WebMercator::default())
+    }
+
+    /// Open a serialized index from a trusted source whose points are projected with the
+    /// given projection.
+    ///
+    /// See [`try_new_trusted`][Self::try_new_trusted] for what is and is not checked.
+    pub fn try_new_trusted_with_projection(
+        bytes: &'a [u8],
+        projection: impl Projection + 'static,
+    ) -> Result<Self, SuperclusterError> {
+        Self::open(bytes, projection, false)
+    }
+
+    fn open(
+        bytes: &'a [u8],
+        projection: impl Projection + 'static,
+        validate: bool,
+    ) -> Result<Self, SuperclusterError> {
+        if !(bytes.as_ptr() as usize).is_multiple_of(8) {
+            return Err(SuperclusterError::InvalidBuffer(
+                "buffer is not aligned to 8 bytes".to_string(),
+            ));
+        }
+
+        let mut reader = Reader::new(bytes);
+        let header = reader.header::<N, P>(&projection)?;
+        let options = header.options;
+        let points = cast(reader.points(header.num_points)?)?;
+        let ids = cast(reader.ids(header.num_points)?)?;
+        if validate {
+            check_ids(ids)?;
+        }
+
+        let levels = (options.min_zoom..=options.max_zoom + 1)
+            .map(|_| reader.level::<N, P>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if levels.last().unwrap().num_items != header.num_points {
+            return Err(SuperclusterError::InvalidBuffer(
+                "full resolution zoom does not match the input points".to_string(),
+            ));
+        }
+        if validate {
+            check_levels(&levels, options.min_zoom, header.num_points)?;
+        }
+        let stored_ids = cast(reader.cluster_ids()?)?;
+        if validate {
+            check_cluster_ids(stored_ids, &levels, options.min_zoom)?;
+        }
+        let levels = levels
+            .into_iter()
+            .map(|level| LevelRef::try_new(level, validate))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let skipped_points = cast(reader.id_list()?)?;
+        let clamped_points = cast(reader.id_list()?)?;
+        if validate {
+            check_ids(skipped_points)?;
+            check_ids(clamped_points)?;
+        }
+
+        Ok(Self {
+            options,
+            points,
+            ids,
+            levels,
+            stored_ids,
+            skipped_points,
+            clamped_points,
+            projection: Arc::new(projection),
+        })
+    }
+
+    /// The ids of points that were left out of the index because of their coordinates.
+    ///
+    /// See [`Supercluster::skipped_points`][crate::Supercluster::skipped_points].
+    pub fn skipped_points(&self) -> &'a [u64] {
+        self.skipped_points
+    }
+
+    /// The ids of points whose coordinates were moved within the bounds of the projection.
+    ///
+    /// See [`Supercluster::clamped_points`][crate::Supercluster::clamped_points].
+    pub fn clamped_points(&self) -> &'a [u64] {
+        self.clamped_points
+    }
+
+    /// Get clusters within a given bounding box and zoom.
+    ///
+    /// See [`Supercluster::get_clusters`][crate::Supercluster::get_clusters].
+    pub fn get_clusters(
+        &self,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters(min_x, min_y, max_x, max_y, zoom)
+    }
+
+    /// Returns the clusters and points inside a polygon at the given zoom.
+    ///
+    /// See [`Supercluster::get_clusters_in_polygon`][crate::Supercluster::get_clusters_in_polygon].
+    pub fn get_clusters_in_polygon(&self, ring: &[(f64, f64)], zoom: usize) -> Vec<ClusterInfo<P>> {
+        self.clusters_in_polygon(ring, &[], zoom)
+    }
+
+    /// Returns the clusters and points inside a [`geo_types::Polygon`] at the given zoom.
+    ///
+    /// See [`Supercluster::get_clusters_in_geo_polygon`][crate::Supercluster::get_clusters_in_geo_polygon].
+    #[cfg(feature = "geo-types")]
+    pub fn get_clusters_in_geo_polygon(
+        &self,
+        polygon: &geo_types::Polygon<f64>,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters_in_geo_polygon(polygon, zoom)
+    }
+
+    /// Returns the children of a cluster (on the next zoom level) given its id.
+    ///
+    /// See [`Supercluster::get_children`][crate::Supercluster::get_children].
+    pub fn get_children(
+        &self,
+        cluster_id: ClusterId,
+    ) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.children(cluster_id)
+    }
+
+    /// Returns all the points of a cluster (given its cluster_id), with pagination support.
+    ///
+    /// See [`Supercluster::get_leaves`][crate::Supercluster::get_leaves].
+    pub fn get_leaves(
+        &self,
+        cluster_id: ClusterId,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.leaves(cluster_id, limit, offset)
+    }
+
+    /// Returns the features within a given XYZ tile.
+    ///
+    /// See [`Supercluster::get_tile`][crate::Supercluster::get_tile].
+    pub fn get_tile(&self, z: usize, x: usize, y: usize) -> Vec<TileFeature<P>> {
+        self.tile(z, x, y)
+    }
+
+    /// Returns the bounding box of the points of a cluster given its id.
+    ///
+    /// See [`Supercluster::get_cluster_bounds`][crate::Supercluster::get_cluster_bounds].
+    pub fn get_cluster_bounds(&self, cluster_id: ClusterId) -> Result<[f64; 4], SuperclusterError> {
+        self.cluster_bounds(cluster_id)
+    }
+
+    /// Returns the convex hull of the points of a cluster given its id.
+    ///
+    /// See [`Supercluster::get_cluster_hull`][crate::Supercluster::get_cluster_hull].
+    pub fn get_cluster_hull(
+        &self,
+        cluster_id: ClusterId,
+    ) -> Result<Vec<(f64, f64)>, SuperclusterError> {
+        self.cluster_hull(cluster_id)
+    }
+
+    /// Returns the cluster containing an input point at the given zoom, given the id of the point.
+    ///
+    /// See [`Supercluster::get_cluster_for_point`][crate::Supercluster::get_cluster_for_point].
+    pub fn get_cluster_for_point(
+        &self,
+        point_id: u64,
+        zoom: usize,
+    ) -> Result<ClusterInfo<P>, SuperclusterError> {
+        self.point_cluster(point_id, zoom)
+    }
+
+    /// Returns the clusters containing an input point given its id.
+    ///
+    /// See [`Supercluster::get_ancestors`][crate::Supercluster::get_ancestors].
+    pub fn get_ancestors(&self, point_id: u64) -> Result<Vec<ClusterInfo<P>>, SuperclusterError> {
+        self.ancestors(point_id)
+    }
+
+    /// Returns every point and cluster at every zoom.
+    ///
+    /// See [`Supercluster::hierarchy`][crate::Supercluster::hierarchy].
+    pub fn hierarchy(&self) -> impl Iterator<Item = HierarchyEntry> + '_ {
+        ClusterIndex::hierarchy(self)
+    }
+
+    /// Returns the point or cluster nearest to `(x, y)` at the given zoom.
+    ///
+    /// See [`Supercluster::nearest`][crate::Supercluster::nearest].
+    pub fn nearest(
+        &self,
+        x: f64,
+        y: f64,
+        zoom: usize,
+        max_pixel_distance: f64,
+    ) -> Option<ClusterInfo<P>> {
+        ClusterIndex::nearest(self, x, y, zoom, max_pixel_distance)
+    }
+
+    /// Returns every point and cluster within `radius_pixels` of `(x, y)` at the given zoom.
+    ///
+    /// See [`Supercluster::get_clusters_within`][crate::Supercluster::get_clusters_within].
+    pub fn get_clusters_within(
+        &self,
+        x: f64,
+        y: f64,
+        radius_pixels: f64,
+        zoom: usize,
+    ) -> Vec<ClusterInfo<P>> {
+        self.clusters_within(x, y, radius_pixels, zoom)
+    }
+
+    /// Returns the zoom on which the cluster expands into several children given the cluster's id.
+    ///
+    /// See [`Supercluster::get_cluster_expansion_zoom`][crate::Supercluster::get_cluster_expansion_zoom].
+    pub fn get_cluster_expansion_zoom(
+        &self,
+        cluster_id: ClusterId,
+    ) -> Result<usize, SuperclusterError> {
+        self.expansion_zoom(cluster_id)
+    }
+}
+
+impl<'a, N: ClusterFloat, P: Pod> ClusterIndex<N, P> for SuperclusterRef<'a, N, P> {
+    type Level = LevelRef<'a, N, P>;
+
+    fn options(&self) -> &SuperclusterOptions {
+        &self.options
+    }
+
+    fn projection(&self) -> &dyn Projection {
+        self.projection.as_ref()
+    }
+
+    fn point(&self, id: usize) -> (f64, f64) {
+        let [x, y] = self.points[id];
+        (x, y)
+    }
+
+    fn point_id(&self, idx: usize) -> u64 {
+        self.ids[idx]
+    }
+
+    fn point_idx(&self, point_id: u64) -> Option<usize> {
+        self.ids.binary_search(&point_id).ok()
+    }
+
+    fn zoom_level(&self, zoom: usize) -> Option<&Self::Level> {
+        self.levels.get(zoom.checked_sub(self.options.min_zoom)?)
+    }
+
+    fn stored_cluster_id(&self, cluster_id: ClusterId) -> Option<ClusterId> {
+        let i = self
+            .stored_ids
+            .binary_search_by_key(&cluster_id.as_u64(), |[public_id, _]| *public_id)
+            .ok()?;
+        Some(ClusterId::from(self.stored_ids[i][1]))
+    }
+}
+
+/// The clusters of a serialized index at a single zoom.
+#[derive(Debug, Clone)]
+pub(crate) struct LevelRef<'a, N: ClusterFloat, P: Pod> {
+    /// The KD-tree buffer, which is empty if there are no indexed items.
+    tree: &'a [u8],
+    x: &'a [N],
+    y: &'a [N],
+    source_id: &'a [u64],
+    parent_id: &'a [u64],
+    num_points: &'a [u64],
+    min_point: &'a [u64],
+    weight: &'a [f64],
+    bounds: &'a [[f64; 4]],
+    category: &'a [u32],
+    /// This is empty if `P` is zero-sized.
+    properties: &'a [P],
+
+    /// Items inserted since the KD-tree was built, which are searched linearly.
+    pending: Range<usize>,
+
+    /// Indices of items whose displayed position differs from their indexed position, in
+    /// ascending order.
+    moved_idx: &'a [u64],
+    moved_x: &'a [N],
+    moved_y: &'a [N],
+}
+
+impl<'a, N: ClusterFloat, P: Pod> LevelRef<'a, N, P> {
+    /// Cast the level's columns, checking the order of moved items if `validate` is set.
+    fn try_new(level: LevelBytes<'a>, validate: bool) -> Result<Self, SuperclusterError> {
+        let moved_idx: &[u64] = cast(level.moved_idx)?;
+        if validate && moved_idx.windows(2).any(|pair| pair[0] >= pair[1])
+            || moved_idx
+                .last()
+                .is_some_and(|i| *i as usize >= level.num_items)
+        {
+            return Err(SuperclusterError::InvalidBuffer(
+                "moved items are out of order".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            tree: level.tree,
+            x: cast(level.x)?,
+            y: cast(level.y)?,
+            source_id: cast(level.source_id)?,
+            parent_id: cast(level.parent_id)?,
+            num_points: cast(level.num_points)?,
+            min_point: cast(level.min_point)?,
+            weight: cast(level.weight)?,
+            bounds: cast(level.bounds)?,
+            category: cast(level.category)?,
+            properties: if size_of::<P>() == 0 {
+                &[]
+            } else {
+                cast(level.properties)?
+            },
+            pending: level.num_indexed..level.num_items,
+            moved_idx,
+            moved_x: cast(level.moved_x)?,
+            moved_y: cast(level.moved_y)?,
+        })
+    }
+
+    fn tree(&self) -> Option<KDTreeRef<'_, N>> {
+        (!self.tree.is_empty()).then(|| KDTreeRef::try_new(&self.tree).unwrap())
+    }
+
+    fn is_removed(&self, i: usize) -> bool {
+        self.num_points[i] == 0
+    }
+
+    fn moved(&self, i: usize) -> Option<usize> {
+        self.moved_idx.binary_search(&(i as u64)).ok()
+    }
+}
+
+impl<'a, N: ClusterFloat, P: Pod> ClusterLevel<N, P> for LevelRef<'a, N, P> {
+    fn anchor(&self, i: usize) -> (N, N) {
+        (self.x[i], self.y[i])
+    }
+
+    fn position(&self, i: usize) -> (N, N) {
+        match self.moved(i) {
+            Some(j) => (self.moved_x[j], self.moved_y[j]),
+            None => self.anchor(i),
+        }
+    }
+
+    fn source_id(&self, i: usize) -> ClusterId {
+        ClusterId::from(self.source_id[i])
+    }
+
+    fn parent_id(&self, i: usize) -> Option<ClusterId> {
+        (self.parent_id[i] != u64::MAX).then(|| ClusterId::from(self.parent_id[i]))
+    }
+
+    fn num_points(&self, i: usize) -> usize {
+        self.num_points[i] as usize
+    }
+
+    fn min_point(&self, i: usize) -> usize {
+        self.min_point[i] as usize
+    }
+
+    fn weight(&self, i: usize) -> f64 {
+        self.weight[i]
+    }
+
+    fn category(&self, i: usize) -> u32 {
+        self.category[i]
+    }
+
+    fn bounds(&self, i: usize) -> [f64; 4] {
+        self.bounds[i]
+    }
+
+    fn properties(&self, i: usize) -> P {
+        if size_of::<P>() == 0 {
+            P::zeroed()
+        } else {
+            self.properties[i]
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    fn within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        let mut ids = match self.tree() {
+            Some(tree) => tree.within(qx, qy, r),
+            None => vec![],
+        };
+        let r2 = r * r;
+        ids.extend(self.pending.clone().filter(|i| {
+            let dx = self.x[*i] - qx;
+            let dy = self.y[*i] - qy;
+            dx * dx + dy * dy <= r2
+        }));
+        ids.retain(|i| !self.is_removed(*i));
+        ids
+    }
+
+    fn displayed_within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        let r2 = r * r;
+        let near = |(x, y): (N, N)| (x - qx) * (x - qx) + (y - qy) * (y - qy) <= r2;
+
+        let mut ids = self.within(qx, qy, r);
+        // Moved items are found by their displayed position rather than their indexed position
+        ids.retain(|i| self.moved(*i).is_none());
+        ids.extend(
+            (0..self.moved_idx.len())
+                .filter(|j| near((self.moved_x[*j], self.moved_y[*j])))
+                .map(|j| self.moved_idx[j] as usize)
+                .filter(|i| !self.is_removed(*i)),
+        );
+        ids
+    }
+
+    fn range(&self, min_x: N, min_y: N, max_x: N, max_y: N) -> Vec<usize> {
+        let contains = |(x, y): (N, N)| x >= min_x && x <= max_x && y >= min_y && y <= max_y;
+
+        let mut ids = match self.tree() {
+            Some(tree) => tree.range(min_x, min_y, max_x, max_y),
+            None => vec![],
+        };
+        ids.extend(
+            self.pending
+                .clone()
+                .filter(|i| contains((self.x[*i], self.y[*i]))),
+        );
+        // Moved items are found by their displayed position rather than their indexed position
+        ids.retain(|i| self.moved(*i).is_none());
+        ids.extend(
+            (0..self.moved_idx.len())
+                .filter(|j| contains((self.moved_x[*j], self.moved_y[*j])))
+                .map(|j| self.moved_idx[j] as usize),
+        );
+        ids.retain(|i| !self.is_removed(*i));
+        ids
+    }
+}
+
+/// Cast a section of a serialized index in place.
+fn cast<T: Pod>(bytes: &[u8]) -> Result<&[T], SuperclusterError> {
+    bytemuck::try_cast_slice(bytes).map_err(|err| {
+        SuperclusterError::InvalidBuffer(format!("could not read section in place: {err}"))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::SuperclusterError;
+    use crate::test::index::build_index;
+    use crate::test::load_fixture::load_places;
+    use crate::{ClusterId, ClusterInfo, Equirectangular, SuperclusterRef};
+
+    /// Copy bytes into a buffer aligned to 8 bytes.
+    fn aligned(bytes: &[u8]) -> Vec<u64> {
+        let mut buffer = vec![0u64; bytes.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<u64, u8>(&mut buffer)[..bytes.len()].copy_from_slice(bytes);
+        buffer
+    }
+
+    #[test]
+    fn test_matches_owned() {
+        let mut supercluster = build_index(&load_places());
+        for id in (0..40).step_by(3) {
+            supercluster.remove(id).unwrap();
+        }
+        let first = supercluster.insert(-79.04, 43.08).unwrap();
+        let last = supercluster.insert(-79.05, 43.09).unwrap();
+        let clamped = supercluster.insert(-79.05, 95.0).unwrap();
+
+        let bytes = supercluster.to_bytes();
+        let buffer = aligned(&bytes);
+        let bytes = &bytemuck::cast_slice(&buffer)[..bytes.len()];
+        let supercluster_ref = SuperclusterRef::<f64>::try_new(bytes).unwrap();
+        assert_eq!(supercluster_ref.clamped_points(), &[clamped]);
+        assert!(supercluster_ref.skipped_points().is_empty());
+
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let ref_clusters = supercluster_ref.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            assert_eq!(clusters.len(), ref_clusters.len());
+
+            for (cluster, ref_cluster) in clusters.iter().zip(ref_clusters.iter()) {
+                assert_eq!(cluster.id(), ref_cluster.id());
+                assert_eq!(cluster.count(), ref_cluster.count());
+                assert_eq!(
+                    (cluster.x(), cluster.y()),
+                    (ref_cluster.x(), ref_cluster.y())
+                );
+                assert_eq!(cluster.bounds(), ref_cluster.bounds());
+                if !cluster.is_cluster() {
+                    continue;
+                }
+
+                assert_eq!(
+                    supercluster.get_cluster_bounds(cluster.id()).unwrap(),
+                    supercluster_ref.get_cluster_bounds(cluster.id()).unwrap()
+                );
+                assert_eq!(
+                    supercluster.get_cluster_hull(cluster.id()).unwrap(),
+                    supercluster_ref.get_cluster_hull(cluster.id()).unwrap()
+                );
+
+                let children = supercluster.get_children(cluster.id()).unwrap();
+                let ref_children = supercluster_ref.get_children(cluster.id()).unwrap();
+                assert_eq!(children.len(), ref_children.len());
+
+                let leaves = supercluster.get_leaves(cluster.id(), None, None).unwrap();
+                let ref_leaves = supercluster_ref
+                    .get_leaves(cluster.id(), None, None)
+                    .unwrap();
+                assert_eq!(leaves.len(), ref_leaves.len());
+
+                assert_eq!(
+                    supercluster
+                        .get_cluster_expansion_zoom(cluster.id())
+                        .unwrap(),
+                    supercluster_ref
+                        .get_cluster_expansion_zoom(cluster.id())
+                        .unwrap()
+                );
+            }
+
+            assert_eq!(
+                supercluster.get_tile(zoom, 0, 0),
+                supercluster_ref.get_tile(zoom, 0, 0)
+            );
+
+            for (x, y) in [(-79.0, 43.0), (10.0, 50.0), (179.0, -10.0)] {
+                assert_eq!(
+                    supercluster.nearest(x, y, zoom, 60.0).map(|c| c.id()),
+                    supercluster_ref.nearest(x, y, zoom, 60.0).map(|c| c.id())
+                );
+                let ids = |clusters: Vec<ClusterInfo>| -> Vec<ClusterId> {
+                    clusters.iter().map(|cluster| cluster.id()).collect()
+                };
+                assert_eq!(
+                    ids(supercluster.get_clusters_within(x, y, 60.0, zoom)),
+                    ids(supercluster_ref.get_clusters_within(x, y, 60.0, zoom))
+                );
+            }
+
+            let ring = [(-100.0, 0.0), (-40.0, 60.0), (-100.0, 60.0)];
+            assert_eq!(
+                supercluster.get_clusters_in_polygon(&ring, zoom).len(),
+                supercluster_ref.get_clusters_in_polygon(&ring, zoom).len()
+            );
+        }
+
+        assert!(supercluster.hierarchy().eq(supercluster_ref.hierarchy()));
+
+        // Including removed, inserted and unknown points
+        for point_id in (0..10).chain(first..=last + 1) {
+            let ids = |ancestors: Vec<ClusterInfo>| -> Vec<ClusterId> {
+                ancestors.iter().map(|cluster| cluster.id()).collect()
+            };
+            assert_eq!(
+                supercluster.get_ancestors(point_id).map(ids).ok(),
+                supercluster_ref.get_ancestors(point_id).map(ids).ok()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unaligned() {
+        let bytes = build_index(&load_places()).to_bytes();
+        let buffer = aligned(&[&[0], &bytes[..]].concat());
+        let unaligned = &bytemuck::cast_slice::<u64, u8>(&buffer)[1..bytes.len() + 1];
+        assert!(SuperclusterRef::<f64>::try_new(unaligned).is_err());
+    }
+
+    #[test]
+    fn test_projection_mismatch() {
+        let bytes = build_index(&load_places()).to_bytes();
+        let buffer = aligned(&bytes);
+        let bytes = &bytemuck::cast_slice(&buffer)[..bytes.len()];
+        assert!(SuperclusterRef::<f64>::try_new(bytes).is_ok());
+        assert!(matches!(
+            SuperclusterRef::<f64>::try_new_with_projection(bytes, Equirectangular),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_trusted() {
+        let supercluster = build_index(&load_places());
+        let bytes = supercluster.to_bytes();
+        let buffer = aligned(&bytes);
+        let bytes = &bytemuck::cast_slice(&buffer)[..bytes.len()];
+        let checked = SuperclusterRef::<f64>::try_new(bytes).unwrap();
+        let trusted = SuperclusterRef::<f64>::try_new_trusted(bytes).unwrap();
+        for zoom in 0..=17 {
+            let clusters = checked.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let trusted_clusters = trusted.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            assert_eq!(
+                clusters.iter().map(|c| c.id()).collect::<Vec<_>>(),
+                trusted_clusters.iter().map(|c| c.id()).collect::<Vec<_>>()
+            );
+        }
+
+        // The header and section sizes are still checked.
+        assert!(SuperclusterRef::<f64>::try_new_trusted(&bytes[..bytes.len() - 8]).is_err());
+        assert!(matches!(
+            SuperclusterRef::<f64>::try_new_trusted_with_projection(bytes, Equirectangular),
+            Err(SuperclusterError::ProjectionMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        let coords = load_places();
+        let mut supercluster = build_index(&coords);
+        let level = supercluster.options.max_zoom - 1;
+        let data = &mut supercluster.trees.get_mut(&level).unwrap().data;
+        data[0].source_id = ClusterId::new(1 << 40, level + 1);
+
+        let buffer = aligned(&supercluster.to_bytes());
+        let bytes = bytemuck::cast_slice::<u64, u8>(&buffer);
+        assert!(matches!(
+            SuperclusterRef::<f64>::try_new(bytes),
+            Err(SuperclusterError::InvalidBuffer(_))
+        ));
+    }
+}
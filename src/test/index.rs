@@ -0,0 +1,34 @@
+use crate::{ClusterAggregator, Supercluster, SuperclusterBuilder};
+
+/// Counts the points in each cluster.
+pub struct PointSum;
+
+impl ClusterAggregator for PointSum {
+    type Accumulator = u32;
+
+    fn map(&self, _point_index: usize) -> Self::Accumulator {
+        1
+    }
+
+    fn reduce(&self, accumulated: &mut Self::Accumulator, other: &Self::Accumulator) {
+        *accumulated += other;
+    }
+}
+
+/// Build an index with default options from longitude and latitude pairs.
+pub fn build_index(coords: &[Vec<f64>]) -> Supercluster {
+    build_index_with_aggregator(coords, &())
+}
+
+/// Build an index with default options from longitude and latitude pairs, accumulating
+/// properties with `aggregator`.
+pub fn build_index_with_aggregator<A: ClusterAggregator>(
+    coords: &[Vec<f64>],
+    aggregator: &A,
+) -> Supercluster<f64, A::Accumulator> {
+    let mut builder = SuperclusterBuilder::new(coords.len());
+    for coord in coords {
+        builder.add(coord[0], coord[1]);
+    }
+    builder.finish_with_aggregator(aggregator)
+}
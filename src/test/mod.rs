@@ -1 +1,2 @@
+pub(crate) mod index;
 pub(crate) mod load_fixture;
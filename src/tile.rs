@@ -0,0 +1,96 @@
+use crate::cluster::ClusterId;
+
+/// A point feature within a single XYZ tile.
+///
+/// Coordinates are integer positions relative to the tile's top-left corner, in units of
+/// [`extent`][crate::SuperclusterOptions::extent]. Features in the tile buffer may have
+/// coordinates slightly outside of `0..extent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileFeature<P = ()> {
+    /// The cluster id if this is a cluster, otherwise the id of the input point.
+    id: ClusterId,
+
+    /// The x position within the tile
+    x: i64,
+
+    /// The y position within the tile
+    y: i64,
+
+    /// If true, references a cluster with containing data. Otherwise, is an original point that
+    /// was added to the index.
+    cluster: bool,
+
+    /// Note: this will always be 1 if `cluster` is false
+    point_count: usize,
+
+    /// The category shared by the points in the cluster
+    category: u32,
+
+    /// The aggregated properties of the cluster, or the mapped properties of a single point.
+    properties: P,
+}
+
+impl<P> TileFeature<P> {
+    pub(crate) fn new(
+        id: ClusterId,
+        x: i64,
+        y: i64,
+        cluster: bool,
+        point_count: usize,
+        category: u32,
+        properties: P,
+    ) -> Self {
+        Self {
+            id,
+            x,
+            y,
+            cluster,
+            point_count,
+            category,
+            properties,
+        }
+    }
+
+    /// If this is a cluster (i.e. [`is_cluster()`][Self::is_cluster] is `true`), the id of the
+    /// cluster.
+    ///
+    /// If this is not a cluster, this references the id of the input point, which is its position
+    /// unless it was added with
+    /// [`SuperclusterBuilder::add_with_id`][crate::SuperclusterBuilder::add_with_id].
+    pub fn id(&self) -> ClusterId {
+        self.id
+    }
+
+    /// The x position of this feature within the tile
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    /// The y position of this feature within the tile
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+
+    /// Whether this object represents a cluster of containing points or a single input point.
+    pub fn is_cluster(&self) -> bool {
+        self.cluster
+    }
+
+    /// The number of points contained in this cluster
+    ///
+    /// This will always be 1 if [`is_cluster`][Self::is_cluster] is `false`.
+    pub fn count(&self) -> usize {
+        self.point_count
+    }
+
+    /// The category of the points in this cluster
+    pub fn category(&self) -> u32 {
+        self.category
+    }
+
+    /// The properties of this cluster, as accumulated by a
+    /// [`ClusterAggregator`][crate::ClusterAggregator].
+    pub fn properties(&self) -> &P {
+        &self.properties
+    }
+}
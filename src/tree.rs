@@ -1,29 +1,243 @@
-use geo_index::kdtree::{KDTreeBuilder, OwnedKDTree};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
 
-use crate::cluster::ClusterData;
+use geo_index::kdtree::{KDTreeBuilder, KDTreeIndex, KDTreeRef, OwnedKDTree};
+
+use crate::cluster::{ClusterData, ClusterId};
+use crate::index::ClusterLevel;
+use crate::r#type::ClusterFloat;
+
+/// A KD-tree buffer in the geo-index format.
+///
+/// Unlike [OwnedKDTree], this can be created from the bytes of a serialized index. The buffer is
+/// stored as `u64` words so that coordinates within it are always aligned.
+#[derive(Debug, Clone)]
+pub(crate) struct TreeBuffer<N: ClusterFloat> {
+    buffer: Vec<u64>,
+    phantom: PhantomData<N>,
+}
+
+impl<N: ClusterFloat> TreeBuffer<N> {
+    /// Copy a KD-tree buffer. The buffer must be a valid geo-index KD-tree of `N`.
+    pub(crate) fn new(bytes: &[u8]) -> Self {
+        let mut buffer = vec![0; bytes.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<u64, u8>(&mut buffer)[..bytes.len()].copy_from_slice(bytes);
+        Self {
+            buffer,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The bytes of the KD-tree buffer.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.buffer)
+    }
+
+    pub(crate) fn as_ref(&self) -> KDTreeRef<'_, N> {
+        KDTreeRef::try_new(self).unwrap()
+    }
+}
+
+impl<N: ClusterFloat> AsRef<[u8]> for TreeBuffer<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<N: ClusterFloat> From<OwnedKDTree<N>> for TreeBuffer<N> {
+    fn from(value: OwnedKDTree<N>) -> Self {
+        Self::new(&value.into_inner())
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct TreeWithData {
-    pub(crate) tree: OwnedKDTree<f64>,
-    pub(crate) data: Vec<ClusterData>,
+pub struct TreeWithData<N: ClusterFloat = f64, P = ()> {
+    /// The KD-tree over the `x` and `y` of `data`. This is `None` if `data` was empty when the
+    /// tree was last indexed.
+    pub(crate) tree: Option<TreeBuffer<N>>,
+    pub(crate) data: Vec<ClusterData<N, P>>,
+
+    /// Indices of items pushed onto `data` since the tree was last indexed. These are searched
+    /// linearly.
+    pub(crate) pending: Vec<usize>,
+
+    /// The displayed position of clusters whose centroid changed since they were indexed.
+    ///
+    /// Incremental updates never move an item within the tree, since the position of a cluster
+    /// is used to find its children and parent. Instead, the updated centroid is stored here.
+    pub(crate) moved: BTreeMap<usize, (N, N)>,
 }
 
-impl TreeWithData {
+impl<N: ClusterFloat, P> TreeWithData<N, P> {
     // This is akin to Supercluster._createTree in the original implementation
-    pub fn new(data: Vec<ClusterData>, node_size: usize) -> Self {
-        let mut tree_builder = KDTreeBuilder::new_with_node_size(data.len(), node_size);
-        for item in data.iter() {
+    pub fn new(data: Vec<ClusterData<N, P>>, node_size: usize) -> Self {
+        let mut tree_with_data = Self {
+            tree: None,
+            data,
+            pending: vec![],
+            moved: BTreeMap::new(),
+        };
+        tree_with_data.reindex(node_size);
+        tree_with_data
+    }
+
+    pub(crate) fn data(&self) -> &[ClusterData<N, P>] {
+        &self.data
+    }
+
+    /// The displayed position of the item at index `i`.
+    pub(crate) fn position(&self, i: usize) -> (N, N) {
+        match self.moved.get(&i) {
+            Some(position) => *position,
+            None => (self.data[i].x, self.data[i].y),
+        }
+    }
+
+    /// Find the indices of all items that have not been removed whose indexed position is within
+    /// `r` of `(qx, qy)`.
+    pub(crate) fn within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        let mut ids = match &self.tree {
+            Some(tree) => tree.as_ref().within(qx, qy, r),
+            None => vec![],
+        };
+        let r2 = r * r;
+        ids.extend(self.pending.iter().copied().filter(|i| {
+            let dx = self.data[*i].x - qx;
+            let dy = self.data[*i].y - qy;
+            dx * dx + dy * dy <= r2
+        }));
+        ids.retain(|i| !self.data[*i].is_removed());
+        ids
+    }
+
+    /// Find the indices of all items that have not been removed whose displayed position is within
+    /// `r` of `(qx, qy)`.
+    pub(crate) fn displayed_within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        let r2 = r * r;
+        let near = |(x, y): (N, N)| (x - qx) * (x - qx) + (y - qy) * (y - qy) <= r2;
+
+        let mut ids = self.within(qx, qy, r);
+        // Moved items are found by their displayed position rather than their indexed position
+        ids.retain(|i| !self.moved.contains_key(i));
+        ids.extend(
+            self.moved
+                .iter()
+                .filter(|(i, position)| near(**position) && !self.data[**i].is_removed())
+                .map(|(i, _)| *i),
+        );
+        ids
+    }
+
+    /// Find the indices of all items that have not been removed whose displayed position is within
+    /// the given bounding box.
+    pub(crate) fn range(&self, min_x: N, min_y: N, max_x: N, max_y: N) -> Vec<usize> {
+        let contains = |(x, y): (N, N)| x >= min_x && x <= max_x && y >= min_y && y <= max_y;
+
+        let mut ids = match &self.tree {
+            Some(tree) => tree.as_ref().range(min_x, min_y, max_x, max_y),
+            None => vec![],
+        };
+        ids.extend(
+            self.pending
+                .iter()
+                .copied()
+                .filter(|i| contains((self.data[*i].x, self.data[*i].y))),
+        );
+        // Moved items are found by their displayed position rather than their indexed position
+        ids.retain(|i| !self.moved.contains_key(i));
+        ids.extend(
+            self.moved
+                .iter()
+                .filter(|(_, position)| contains(**position))
+                .map(|(i, _)| *i),
+        );
+        ids.retain(|i| !self.data[*i].is_removed());
+        ids
+    }
+
+    /// Append an item, returning its index.
+    ///
+    /// The item is searched linearly until enough items are pending that the tree is rebuilt.
+    pub(crate) fn push(&mut self, item: ClusterData<N, P>, node_size: usize) -> usize {
+        let idx = self.data.len();
+        self.data.push(item);
+        self.pending.push(idx);
+        if self.pending.len() > usize::max(node_size, self.data.len() / 16) {
+            self.reindex(node_size);
+        }
+        idx
+    }
+
+    /// Rebuild the KD-tree over all items, including pending items.
+    fn reindex(&mut self, node_size: usize) {
+        self.pending.clear();
+        if self.data.is_empty() {
+            self.tree = None;
+            return;
+        }
+
+        let mut tree_builder = KDTreeBuilder::new_with_node_size(self.data.len(), node_size);
+        for item in self.data.iter() {
             tree_builder.add(item.x(), item.y());
         }
-        let tree = tree_builder.finish();
-        Self { tree, data }
+        self.tree = Some(tree_builder.finish().into());
     }
+}
 
-    pub(crate) fn data(&self) -> &[ClusterData] {
-        &self.data
+impl<N: ClusterFloat, P: Clone> ClusterLevel<N, P> for TreeWithData<N, P> {
+    fn anchor(&self, i: usize) -> (N, N) {
+        (self.data[i].x, self.data[i].y)
+    }
+
+    fn position(&self, i: usize) -> (N, N) {
+        TreeWithData::position(self, i)
+    }
+
+    fn source_id(&self, i: usize) -> ClusterId {
+        self.data[i].source_id
+    }
+
+    fn parent_id(&self, i: usize) -> Option<ClusterId> {
+        self.data[i].parent_id
+    }
+
+    fn num_points(&self, i: usize) -> usize {
+        self.data[i].num_points
+    }
+
+    fn min_point(&self, i: usize) -> usize {
+        self.data[i].min_point
+    }
+
+    fn weight(&self, i: usize) -> f64 {
+        self.data[i].weight
+    }
+
+    fn category(&self, i: usize) -> u32 {
+        self.data[i].category
+    }
+
+    fn bounds(&self, i: usize) -> [f64; 4] {
+        self.data[i].bounds
+    }
+
+    fn properties(&self, i: usize) -> P {
+        self.data[i].properties.clone()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        TreeWithData::within(self, qx, qy, r)
+    }
+
+    fn displayed_within(&self, qx: N, qy: N, r: N) -> Vec<usize> {
+        TreeWithData::displayed_within(self, qx, qy, r)
     }
 
-    pub(crate) fn tree(&self) -> &OwnedKDTree<f64> {
-        &self.tree
+    fn range(&self, min_x: N, min_y: N, max_x: N, max_y: N) -> Vec<usize> {
+        TreeWithData::range(self, min_x, min_y, max_x, max_y)
     }
 }
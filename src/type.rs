@@ -0,0 +1,21 @@
+use geo_index::IndexableNum;
+use num_traits::{Float, NumCast};
+
+/// A trait for floating point types that can be used to store projected coordinates.
+///
+/// This is implemented for `f32` and `f64`, the floating point types supported by
+/// [geo-index](https://github.com/kylebarron/geo-index). Using `f32` roughly halves the memory
+/// used by the index at the cost of precision at very high zoom levels.
+pub trait ClusterFloat: IndexableNum + Float {
+    /// Convert an `f64` to this type.
+    fn from_f64(value: f64) -> Self {
+        <Self as NumCast>::from(value).unwrap()
+    }
+
+    /// Convert this value to an `f64`.
+    fn as_f64(self) -> f64 {
+        self.to_f64().unwrap()
+    }
+}
+
+impl<T: IndexableNum + Float> ClusterFloat for T {}
@@ -0,0 +1,847 @@
+use crate::aggregator::ClusterAggregator;
+use crate::builder::{CoordinateAction, HierarchyBuilder};
+use crate::cluster::{ClusterData, ClusterId};
+use crate::error::SuperclusterError;
+use crate::index::ClusterIndex;
+use crate::r#type::ClusterFloat;
+use crate::tree::TreeWithData;
+use crate::util::extend_bounds;
+use crate::Supercluster;
+
+/// The count, weight, indexed and displayed centroids, bounding box, position of the point with
+/// the smallest id and properties of a group of items.
+type Aggregate<P> = (usize, f64, (f64, f64), (f64, f64), [f64; 4], usize, P);
+
+impl<N: ClusterFloat> Supercluster<N> {
+    /// Insert a point into the index, returning its id.
+    ///
+    /// The id of the new point is one more than the largest id in the index, which is its
+    /// position unless points were added with
+    /// [`SuperclusterBuilder::add_with_id`][crate::SuperclusterBuilder::add_with_id].
+    ///
+    /// The point is in the same units as the points added to the
+    /// [SuperclusterBuilder][crate::SuperclusterBuilder]. Only the clusters near the point are
+    /// updated at each zoom, so the result may differ slightly from rebuilding the index. Use
+    /// [`compact`][Self::compact] to periodically rebuild it.
+    ///
+    /// Points with non-finite or out of range coordinates are handled with the
+    /// [CoordinatePolicy][crate::CoordinatePolicy] the index was built with, and listed by
+    /// [`skipped_points`][Self::skipped_points] or [`clamped_points`][Self::clamped_points].
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`SuperclusterError::InvalidCoordinate`] if the coordinate policy rejects the point,
+    /// and leaves the index unchanged.
+    ///
+    /// Returns [`SuperclusterError::NoClusterFound`] if a cluster affected by the update can't be
+    /// found, which means that the index is inconsistent, for example because it was built with
+    /// different options.
+    pub fn insert(&mut self, x: f64, y: f64) -> Result<u64, SuperclusterError> {
+        self.insert_with_aggregator(x, y, &())
+    }
+
+    /// Insert a point with the given weight into the index, returning its id. See
+    /// [`insert`][Self::insert] and
+    /// [`SuperclusterBuilder::add_weighted`][crate::SuperclusterBuilder::add_weighted].
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`SuperclusterError::InvalidWeight`] if `weight` is not positive and finite, and
+    /// leaves the index unchanged.
+    pub fn insert_weighted(
+        &mut self,
+        x: f64,
+        y: f64,
+        weight: f64,
+    ) -> Result<u64, SuperclusterError> {
+        self.insert_weighted_with_aggregator(x, y, weight, &())
+    }
+
+    /// Insert a point in the given category into the index, returning its id. See
+    /// [`insert`][Self::insert] and
+    /// [`SuperclusterBuilder::add_with_category`][crate::SuperclusterBuilder::add_with_category].
+    pub fn insert_with_category(
+        &mut self,
+        x: f64,
+        y: f64,
+        category: u32,
+    ) -> Result<u64, SuperclusterError> {
+        self.insert_weighted_with_category_and_aggregator(x, y, 1.0, category, &())
+    }
+
+    /// Remove a point from the index, given its id as returned by [`ClusterInfo::id`] for leaves.
+    ///
+    /// Ids of the remaining points are unchanged. Clusters that no longer have enough points are
+    /// dissolved.
+    ///
+    /// Returns [`SuperclusterError::NoPointFound`] if there is no point with this id, and
+    /// otherwise errors like [`insert`][Self::insert].
+    ///
+    /// [`ClusterInfo::id`]: crate::ClusterInfo::id
+    pub fn remove(&mut self, id: u64) -> Result<(), SuperclusterError> {
+        self.remove_with_aggregator(id, &())
+    }
+
+    /// Rebuild the clusters at every zoom from the points currently in the index.
+    ///
+    /// This undoes any drift caused by incremental updates and drops removed clusters from the
+    /// trees. Removed points keep their place in the full resolution tree, so that the ids of the
+    /// remaining points are unchanged, but they are not part of any cluster. Cluster ids may
+    /// change.
+    pub fn compact(&mut self) {
+        self.compact_with_aggregator(&())
+    }
+}
+
+impl<N: ClusterFloat, P: Clone> Supercluster<N, P> {
+    /// Insert a point into the index, accumulating its properties with the provided
+    /// [ClusterAggregator]. See [`insert`][Supercluster::insert].
+    ///
+    /// The aggregator should be the one this index was built with. It is called with the number
+    /// of points added and inserted before the new point, which continues the positions returned
+    /// by [`SuperclusterBuilder::add`][crate::SuperclusterBuilder::add]. See
+    /// [`ClusterAggregator::map`].
+    pub fn insert_with_aggregator<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        x: f64,
+        y: f64,
+        aggregator: &A,
+    ) -> Result<u64, SuperclusterError> {
+        self.insert_weighted_with_aggregator(x, y, 1.0, aggregator)
+    }
+
+    /// Insert a point with the given weight into the index, accumulating its properties with the
+    /// provided [ClusterAggregator]. See [`insert_weighted`][Supercluster::insert_weighted].
+    pub fn insert_weighted_with_aggregator<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        x: f64,
+        y: f64,
+        weight: f64,
+        aggregator: &A,
+    ) -> Result<u64, SuperclusterError> {
+        self.insert_weighted_with_category_and_aggregator(x, y, weight, 0, aggregator)
+    }
+
+    /// Insert a point with the given weight and category into the index, accumulating its
+    /// properties with the provided [ClusterAggregator]. See
+    /// [`insert_weighted`][Supercluster::insert_weighted] and
+    /// [`insert_with_category`][Supercluster::insert_with_category].
+    pub fn insert_weighted_with_category_and_aggregator<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        x: f64,
+        y: f64,
+        weight: f64,
+        category: u32,
+        aggregator: &A,
+    ) -> Result<u64, SuperclusterError> {
+        if !(weight.is_finite() && weight > 0.0) {
+            return Err(SuperclusterError::InvalidWeight {
+                index: self.points.len(),
+                weight,
+            });
+        }
+
+        // Inserted points keep the ids in ascending order
+        let id = self.ids.last().map_or(0, |id| id + 1);
+        if id > ClusterId::MAX_POINT_ID {
+            return Err(SuperclusterError::InvalidPointId(id));
+        }
+
+        let position = self.points.len();
+        let mut point = (x, y);
+        let action =
+            self.coordinate_policy
+                .apply(position, &mut point, self.projection.as_ref())?;
+        let mut item = ClusterData::new_point(
+            point,
+            self.projection.project(point.0, point.1),
+            ClusterId::new_source_id(position),
+            weight,
+            category,
+            aggregator.map(position),
+        );
+        self.points.push(point);
+        self.ids.push(id);
+
+        // Skipped points are kept as removed items, like those of the builder
+        if action == CoordinateAction::Skip {
+            (item.x, item.y) = (N::zero(), N::zero());
+            item.num_points = 0;
+            item.weight = 0.0;
+        }
+
+        let level = self.options.max_zoom + 1;
+        let node_size = self.options.node_size;
+        let idx = self.level_mut(level).push(item, node_size);
+        debug_assert_eq!(idx, position);
+        match action {
+            CoordinateAction::Keep => self.place(level, idx, aggregator)?,
+            CoordinateAction::Skip => self.skipped_points.push(id),
+            CoordinateAction::Clamp => {
+                self.clamped_points.push(id);
+                self.place(level, idx, aggregator)?;
+            }
+        }
+
+        Ok(id)
+    }
+
+    /// Remove a point from the index, updating cluster properties with the provided
+    /// [ClusterAggregator]. See [`remove`][Supercluster::remove].
+    pub fn remove_with_aggregator<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        id: u64,
+        aggregator: &A,
+    ) -> Result<(), SuperclusterError> {
+        let level = self.options.max_zoom + 1;
+        let Ok(position) = self.ids.binary_search(&id) else {
+            return Err(SuperclusterError::NoPointFound);
+        };
+        if self.level(level).data[position].is_removed() {
+            return Err(SuperclusterError::NoPointFound);
+        }
+
+        self.retire(level, position, aggregator)
+    }
+
+    /// Rebuild the clusters at every zoom, accumulating properties with the provided
+    /// [ClusterAggregator]. See [`compact`][Supercluster::compact].
+    pub fn compact_with_aggregator<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        aggregator: &A,
+    ) {
+        let level = self.options.max_zoom + 1;
+        let mut data = std::mem::take(&mut self.level_mut(level).data);
+        for item in data.iter_mut() {
+            item.zoom = None;
+            item.parent_id = None;
+        }
+
+        self.trees = HierarchyBuilder::new(self.options, aggregator).build(data);
+        self.index_cluster_ids();
+    }
+
+    fn level(&self, level: usize) -> &TreeWithData<N, P> {
+        &self.trees[&level]
+    }
+
+    fn level_mut(&mut self, level: usize) -> &mut TreeWithData<N, P> {
+        self.trees.get_mut(&level).unwrap()
+    }
+
+    /// Represent the item at index `idx` of `level` on the next lower zoom.
+    ///
+    /// The item joins the nearest cluster of its category whose origin is within the cluster
+    /// radius, or forms a new cluster with its unclustered neighbors of the same category if they
+    /// weigh enough. Otherwise, it is
+    /// copied to the next zoom unclustered.
+    fn place<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        level: usize,
+        idx: usize,
+        aggregator: &A,
+    ) -> Result<(), SuperclusterError> {
+        if level == self.options.min_zoom {
+            return Ok(());
+        }
+
+        let zoom = level - 1;
+        let tree = self.level(level);
+        let (x, y) = (tree.data[idx].x, tree.data[idx].y);
+        let category = tree.data[idx].category;
+        let r = N::from_f64(self.options.zoom_radius(zoom));
+        let neighbor_ids: Vec<usize> = tree
+            .within(x, y, r)
+            .into_iter()
+            .filter(|neighbor_id| {
+                *neighbor_id != idx && tree.data[*neighbor_id].category == category
+            })
+            .collect();
+
+        let nearest_parent = neighbor_ids
+            .iter()
+            .filter_map(|neighbor_id| {
+                let neighbor = &tree.data[*neighbor_id];
+                neighbor
+                    .parent_id
+                    .filter(|parent_id| self.is_origin(*parent_id, level, *neighbor_id))
+                    .map(|parent_id| {
+                        let (dx, dy) = (neighbor.x - x, neighbor.y - y);
+                        ((dx * dx + dy * dy).as_f64(), parent_id)
+                    })
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        if let Some((_, parent_id)) = nearest_parent {
+            self.level_mut(level).data[idx].parent_id = Some(parent_id);
+            let cluster_idx = self
+                .find_cluster(zoom, parent_id)
+                .ok_or(SuperclusterError::NoClusterFound)?;
+            return self.refresh(zoom, cluster_idx, aggregator);
+        }
+
+        let mut children = vec![idx];
+        children.extend(
+            neighbor_ids
+                .into_iter()
+                .filter(|neighbor_id| tree.data[*neighbor_id].parent_id.is_none()),
+        );
+        let (num_points, weight, anchor, position, bounds, min_point, properties) =
+            self.aggregate(level, &children, aggregator);
+
+        let node_size = self.options.node_size;
+        let min_weight = self.options.min_points as f64;
+        let (new_idx, position) = if children.len() > 1 && weight >= min_weight {
+            let id = ClusterId::new(idx as u64, zoom);
+            self.stored_ids
+                .insert(ClusterId::new(self.ids[min_point], zoom), id);
+            for child in &children {
+                self.level_mut(level).data[*child].parent_id = Some(id);
+            }
+
+            // The neighbors are now represented by the new cluster
+            for child in &children[1..] {
+                let neighbor = &self.level(level).data[*child];
+                if let Some(copy) = self.find_copy(zoom, neighbor.source_id, neighbor.x, neighbor.y)
+                {
+                    self.retire(zoom, copy, aggregator)?;
+                }
+            }
+
+            let cluster = ClusterData {
+                x: N::from_f64(anchor.0),
+                y: N::from_f64(anchor.1),
+                zoom: None,
+                source_id: id,
+                parent_id: None,
+                num_points,
+                min_point,
+                weight,
+                category,
+                bounds,
+                properties,
+            };
+            (self.level_mut(zoom).push(cluster, node_size), position)
+        } else {
+            // left unclustered
+            let mut item = self.level(level).data[idx].clone();
+            item.zoom = None;
+            item.parent_id = None;
+            let (x, y) = self.level(level).position(idx);
+            let new_idx = self.level_mut(zoom).push(item, node_size);
+            (new_idx, (x.as_f64(), y.as_f64()))
+        };
+
+        self.set_position(zoom, new_idx, position);
+        self.place(zoom, new_idx, aggregator)
+    }
+
+    /// Remove the item at index `idx` of `level`, and its representation at lower zooms.
+    fn retire<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        level: usize,
+        idx: usize,
+        aggregator: &A,
+    ) -> Result<(), SuperclusterError> {
+        let tree = self.level_mut(level);
+        tree.moved.remove(&idx);
+        let item = &mut tree.data[idx];
+        item.num_points = 0;
+        item.weight = 0.0;
+        let (x, y, source_id, parent_id) = (item.x, item.y, item.source_id, item.parent_id);
+
+        if level == self.options.min_zoom {
+            return Ok(());
+        }
+
+        match parent_id {
+            Some(parent_id) => {
+                let cluster_idx = self
+                    .find_cluster(level - 1, parent_id)
+                    .ok_or(SuperclusterError::NoClusterFound)?;
+                self.refresh(level - 1, cluster_idx, aggregator)
+            }
+            None => match self.find_copy(level - 1, source_id, x, y) {
+                Some(copy) => self.retire(level - 1, copy, aggregator),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Recompute the cluster at index `idx` of `level` from its children, dissolving it if it no
+    /// longer weighs enough.
+    fn refresh<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        level: usize,
+        idx: usize,
+        aggregator: &A,
+    ) -> Result<(), SuperclusterError> {
+        let id = self.level(level).data[idx].source_id;
+        let children = self.find_children(level, id);
+        let (num_points, weight, _, position, bounds, min_point, properties) =
+            self.aggregate(level + 1, &children, aggregator);
+
+        if children.len() < 2 || weight < self.options.min_points as f64 {
+            self.retire(level, idx, aggregator)?;
+            for child in &children {
+                self.level_mut(level + 1).data[*child].parent_id = None;
+            }
+            for child in children {
+                // The child may have been claimed by a cluster formed by a previous child
+                if self.level(level + 1).data[child].parent_id.is_none() {
+                    self.place(level + 1, child, aggregator)?;
+                }
+            }
+            return Ok(());
+        }
+
+        // The public id of the cluster changes if its point with the smallest id was removed
+        let old_min_point = self.level(level).data[idx].min_point;
+        if min_point != old_min_point {
+            let zoom = id.get_origin_zoom() - 1;
+            let old_id = ClusterId::new(self.ids[old_min_point], zoom);
+            if self.stored_ids.get(&old_id) == Some(&id) {
+                self.stored_ids.remove(&old_id);
+            }
+            self.stored_ids
+                .insert(ClusterId::new(self.ids[min_point], zoom), id);
+        }
+
+        let item = &mut self.level_mut(level).data[idx];
+        item.num_points = num_points;
+        item.min_point = min_point;
+        item.weight = weight;
+        item.bounds = bounds;
+        item.properties = properties;
+        self.set_position(level, idx, position);
+        self.propagate(level, idx, aggregator)
+    }
+
+    /// Update the representation at lower zooms of the item at index `idx` of `level`, after its
+    /// count, weight, position or properties changed.
+    fn propagate<A: ClusterAggregator<Accumulator = P>>(
+        &mut self,
+        level: usize,
+        idx: usize,
+        aggregator: &A,
+    ) -> Result<(), SuperclusterError> {
+        if level == self.options.min_zoom {
+            return Ok(());
+        }
+
+        let tree = self.level(level);
+        let item = &tree.data[idx];
+        if let Some(parent_id) = item.parent_id {
+            let cluster_idx = self
+                .find_cluster(level - 1, parent_id)
+                .ok_or(SuperclusterError::NoClusterFound)?;
+            self.refresh(level - 1, cluster_idx, aggregator)
+        } else if let Some(copy) = self.find_copy(level - 1, item.source_id, item.x, item.y) {
+            let (num_points, weight, bounds) = (item.num_points, item.weight, item.bounds);
+            let min_point = item.min_point;
+            let properties = item.properties.clone();
+            let (x, y) = tree.position(idx);
+
+            let copy_item = &mut self.level_mut(level - 1).data[copy];
+            copy_item.num_points = num_points;
+            copy_item.min_point = min_point;
+            copy_item.weight = weight;
+            copy_item.bounds = bounds;
+            copy_item.properties = properties;
+            self.set_position(level - 1, copy, (x.as_f64(), y.as_f64()));
+            self.propagate(level - 1, copy, aggregator)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the item at index `idx` of `level` is the origin of the cluster `cluster_id`.
+    fn is_origin(&self, cluster_id: ClusterId, level: usize, idx: usize) -> bool {
+        cluster_id.get_origin_zoom() == level && cluster_id.get_origin_idx() == idx
+    }
+
+    /// Find the indices of the children of the cluster `cluster_id` within `level + 1`.
+    fn find_children(&self, level: usize, cluster_id: ClusterId) -> Vec<usize> {
+        let tree = self.level(level + 1);
+        let origin = &tree.data[cluster_id.get_origin_idx()];
+        let r = N::from_f64(self.options.zoom_radius(level));
+        tree.within(origin.x, origin.y, r)
+            .into_iter()
+            .filter(|i| tree.data[*i].parent_id == Some(cluster_id))
+            .collect()
+    }
+
+    /// Accumulate the count, weight, indexed and displayed centroids, bounding box, point with the
+    /// smallest id and properties of the given items of `level`.
+    fn aggregate<A: ClusterAggregator<Accumulator = P>>(
+        &self,
+        level: usize,
+        ids: &[usize],
+        aggregator: &A,
+    ) -> Aggregate<P> {
+        let tree = self.level(level);
+        let mut properties = tree.data[ids[0]].properties.clone();
+        let mut num_points = 0;
+        let mut total = 0.0;
+        let (mut ax, mut ay, mut px, mut py) = (0.0, 0.0, 0.0, 0.0);
+        let mut bounds = [
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        let mut min_point = usize::MAX;
+
+        for (i, id) in ids.iter().enumerate() {
+            let item = &tree.data[*id];
+            if i > 0 {
+                aggregator.reduce(&mut properties, &item.properties);
+            }
+
+            let weight = item.weight;
+            let (x, y) = tree.position(*id);
+            num_points += item.num_points;
+            total += weight;
+            ax += item.x.as_f64() * weight;
+            ay += item.y.as_f64() * weight;
+            px += x.as_f64() * weight;
+            py += y.as_f64() * weight;
+            if !item.is_removed() {
+                extend_bounds(&mut bounds, &item.bounds);
+                min_point = min_point.min(item.min_point);
+            }
+        }
+
+        (
+            num_points,
+            total,
+            (ax / total, ay / total),
+            (px / total, py / total),
+            bounds,
+            min_point,
+            properties,
+        )
+    }
+
+    /// Set the displayed position of the item at index `idx` of `level`.
+    fn set_position(&mut self, level: usize, idx: usize, position: (f64, f64)) {
+        let tree = self.level_mut(level);
+        let position = (N::from_f64(position.0), N::from_f64(position.1));
+        if position == (tree.data[idx].x, tree.data[idx].y) {
+            tree.moved.remove(&idx);
+        } else {
+            tree.moved.insert(idx, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aggregator::ClusterAggregator;
+    use crate::error::SuperclusterError;
+    use crate::test::index::{build_index, build_index_with_aggregator, PointSum};
+    use crate::test::load_fixture::load_places;
+    use crate::util::extend_bounds;
+    use crate::{ClusterId, CoordinatePolicy, Supercluster, SuperclusterBuilder};
+
+    /// Check that every zoom accounts for all `num_points` points, that the children of every
+    /// cluster add up to its count, that its bounds are those of its leaves, and that it can be
+    /// found at its position.
+    fn check_consistency<P: Clone>(supercluster: &Supercluster<f64, P>, num_points: usize) {
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let total: usize = clusters.iter().map(|cluster| cluster.count()).sum();
+            assert_eq!(total, num_points, "zoom {zoom}");
+
+            for cluster in clusters.iter().filter(|cluster| cluster.is_cluster()) {
+                let children = supercluster.get_children(cluster.id()).unwrap();
+                let total: usize = children.iter().map(|child| child.count()).sum();
+                assert_eq!(total, cluster.count(), "zoom {zoom}");
+
+                let leaves = supercluster
+                    .get_leaves(cluster.id(), Some(usize::MAX), None)
+                    .unwrap();
+                assert_eq!(leaves.len(), cluster.count(), "zoom {zoom}");
+
+                let mut bounds = leaves[0].bounds();
+                for leaf in &leaves {
+                    extend_bounds(&mut bounds, &leaf.bounds());
+                }
+                assert_eq!(cluster.bounds(), bounds, "zoom {zoom}");
+
+                // Clusters are found at their displayed position, even if it moved
+                let nearest = supercluster.nearest(cluster.x(), cluster.y(), zoom, 1e-6);
+                assert!(nearest.is_some(), "zoom {zoom}");
+            }
+        }
+    }
+
+    /// Sorted cluster counts at every zoom.
+    fn cluster_counts(supercluster: &Supercluster) -> Vec<Vec<usize>> {
+        (0..=17)
+            .map(|zoom| {
+                let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+                let mut counts: Vec<usize> =
+                    clusters.iter().map(|cluster| cluster.count()).collect();
+                counts.sort();
+                counts
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_insert() {
+        let coords = load_places();
+        let (initial, inserted) = coords.split_at(coords.len() / 2);
+        let mut builder = SuperclusterBuilder::new(initial.len());
+        for coord in initial {
+            builder.add(coord[0], coord[1]);
+        }
+        let mut supercluster = builder.finish();
+
+        for (i, coord) in inserted.iter().enumerate() {
+            let id = supercluster.insert(coord[0], coord[1]).unwrap();
+            assert_eq!(id, (initial.len() + i) as u64);
+        }
+        check_consistency(&supercluster, coords.len());
+
+        let leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        let leaf = leaves
+            .iter()
+            .find(|leaf| leaf.id().as_usize() == 100)
+            .unwrap();
+        assert_eq!((leaf.x(), leaf.y()), (coords[100][0], coords[100][1]));
+    }
+
+    #[test]
+    fn test_insert_weighted() {
+        let coords = load_places();
+        let mut supercluster = build_index(&coords);
+        let id = supercluster
+            .insert_weighted(coords[0][0], coords[0][1], 10.0)
+            .unwrap();
+        check_consistency(&supercluster, coords.len() + 1);
+
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            let weight: f64 = clusters.iter().map(|cluster| cluster.weight()).sum();
+            assert_eq!(weight, coords.len() as f64 + 10.0, "zoom {zoom}");
+        }
+
+        supercluster.remove(id).unwrap();
+        check_consistency(&supercluster, coords.len());
+
+        for weight in [f64::NAN, 0.0, -1.0] {
+            assert!(matches!(
+                supercluster.insert_weighted(coords[0][0], coords[0][1], weight),
+                Err(SuperclusterError::InvalidWeight { .. })
+            ));
+        }
+        check_consistency(&supercluster, coords.len());
+    }
+
+    #[test]
+    fn test_aggregator_point_index() {
+        /// Maps each point to its value, by the order points were added and inserted in.
+        struct Values(Vec<u32>);
+
+        impl ClusterAggregator for Values {
+            type Accumulator = u32;
+
+            fn map(&self, point_index: usize) -> Self::Accumulator {
+                self.0[point_index]
+            }
+
+            fn reduce(&self, accumulated: &mut Self::Accumulator, other: &Self::Accumulator) {
+                *accumulated += other;
+            }
+        }
+
+        // Ids in the opposite order of the points, which are far apart
+        let mut builder = SuperclusterBuilder::new(3);
+        for (i, x) in [-100.0, 0.0, 100.0].into_iter().enumerate() {
+            builder.add_with_id(x, 0.0, 10 - i as u64);
+        }
+        let values = Values(vec![1, 2, 3, 4]);
+        let mut supercluster = builder.finish_with_aggregator(&values);
+        let id = supercluster
+            .insert_with_aggregator(50.0, 50.0, &values)
+            .unwrap();
+
+        let leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        let mut properties: Vec<(u64, u32)> = leaves
+            .iter()
+            .map(|leaf| (leaf.id().as_u64(), *leaf.properties()))
+            .collect();
+        properties.sort();
+        assert_eq!(properties, vec![(8, 3), (9, 2), (10, 1), (id, 4)]);
+    }
+
+    #[test]
+    fn test_insert_coordinate_policy() {
+        let coords = load_places();
+        let build = |policy| {
+            let mut builder = SuperclusterBuilder::new(coords.len()).with_coordinate_policy(policy);
+            for coord in &coords {
+                builder.add(coord[0], coord[1]);
+            }
+            builder.finish()
+        };
+
+        // Clamped points wrap around the antimeridian
+        let mut supercluster = build(CoordinatePolicy::Clamp);
+        let id = supercluster.insert(-200.0, 10.0).unwrap();
+        assert_eq!(supercluster.clamped_points(), &[id]);
+        let leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        let leaf = leaves.iter().find(|leaf| leaf.id().as_u64() == id).unwrap();
+        assert_eq!((leaf.x(), leaf.y()), (160.0, 10.0));
+        assert!(matches!(
+            supercluster.insert(f64::NAN, 0.0),
+            Err(SuperclusterError::InvalidCoordinate { .. })
+        ));
+        check_consistency(&supercluster, coords.len() + 1);
+
+        let mut supercluster = build(CoordinatePolicy::Skip);
+        let id = supercluster.insert(f64::NAN, 0.0).unwrap();
+        assert_eq!(supercluster.skipped_points(), &[id]);
+        supercluster.insert(0.0, 0.0).unwrap();
+        check_consistency(&supercluster, coords.len() + 1);
+
+        let mut supercluster = build(CoordinatePolicy::Reject);
+        assert!(matches!(
+            supercluster.insert(0.0, 95.0),
+            Err(SuperclusterError::InvalidCoordinate { .. })
+        ));
+        check_consistency(&supercluster, coords.len());
+    }
+
+    #[test]
+    fn test_insert_with_category() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        for (i, coord) in coords.iter().enumerate() {
+            builder.add_with_category(coord[0], coord[1], (i % 2) as u32);
+        }
+        let mut supercluster = builder.finish();
+        for (i, coord) in coords.iter().enumerate().step_by(5) {
+            supercluster
+                .insert_with_category(coord[0], coord[1], (i % 2 + 1) as u32)
+                .unwrap();
+        }
+        let num_points = coords.len() + coords.len().div_ceil(5);
+        check_consistency(&supercluster, num_points);
+
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in clusters.iter().filter(|cluster| cluster.is_cluster()) {
+                let children = supercluster.get_children(cluster.id()).unwrap();
+                for child in children {
+                    assert_eq!(child.category(), cluster.category(), "zoom {zoom}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let coords = load_places();
+        let mut supercluster = build_index(&coords);
+
+        let mut num_points = coords.len();
+        for id in (0..coords.len() as u64).step_by(3) {
+            supercluster.remove(id).unwrap();
+            num_points -= 1;
+        }
+        check_consistency(&supercluster, num_points);
+
+        assert!(supercluster.remove(0).is_err());
+        assert!(supercluster.remove(coords.len() as u64).is_err());
+    }
+
+    #[test]
+    fn test_inconsistent_index() {
+        let mut builder = SuperclusterBuilder::new(2);
+        builder.add(10.0, 10.0);
+        builder.add(50.0, 50.0);
+        let mut supercluster = builder.finish();
+
+        // Refer to a cluster that doesn't exist
+        let level = supercluster.options.max_zoom + 1;
+        let data = &mut supercluster.trees.get_mut(&level).unwrap().data;
+        data[0].parent_id = Some(ClusterId::new(1, level - 1));
+
+        assert!(matches!(
+            supercluster.remove(0),
+            Err(SuperclusterError::NoClusterFound)
+        ));
+    }
+
+    #[test]
+    fn test_remove_with_point_ids() {
+        let coords = load_places();
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        for (i, coord) in coords.iter().enumerate() {
+            builder.add_with_id(coord[0], coord[1], 10 * i as u64);
+        }
+        let mut supercluster = builder.finish();
+
+        assert!(supercluster.remove(5).is_err());
+        supercluster.remove(50).unwrap();
+        assert!(supercluster.remove(50).is_err());
+        check_consistency(&supercluster, coords.len() - 1);
+
+        let id = supercluster.insert(coords[5][0], coords[5][1]).unwrap();
+        assert_eq!(id, 10 * (coords.len() as u64 - 1) + 1);
+        let leaves = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, 17);
+        assert!(leaves.iter().any(|leaf| leaf.id().as_u64() == id));
+        assert!(leaves.iter().all(|leaf| leaf.id().as_u64() != 50));
+        check_consistency(&supercluster, coords.len());
+    }
+
+    #[test]
+    fn test_compact() {
+        let coords = load_places();
+        let mut supercluster = build_index(&coords);
+        for id in (0..coords.len() as u64).step_by(2) {
+            supercluster.remove(id).unwrap();
+        }
+        for coord in coords.iter().step_by(2) {
+            supercluster.insert(coord[0], coord[1]).unwrap();
+        }
+        supercluster.compact();
+        check_consistency(&supercluster, coords.len());
+
+        // Compacting clusters the remaining points in the order they were added
+        let mut builder = SuperclusterBuilder::new(coords.len());
+        for coord in coords.iter().skip(1).step_by(2) {
+            builder.add(coord[0], coord[1]);
+        }
+        for coord in coords.iter().step_by(2) {
+            builder.add(coord[0], coord[1]);
+        }
+        let expected = builder.finish();
+        assert_eq!(cluster_counts(&supercluster), cluster_counts(&expected));
+    }
+
+    #[test]
+    fn test_update_aggregator() {
+        let coords = load_places();
+        let mut supercluster = build_index_with_aggregator(&coords, &PointSum);
+        for id in (0..coords.len() as u64).step_by(4) {
+            supercluster.remove_with_aggregator(id, &PointSum).unwrap();
+        }
+        for coord in coords.iter().step_by(4) {
+            supercluster
+                .insert_with_aggregator(coord[0] + 0.5, coord[1], &PointSum)
+                .unwrap();
+        }
+        check_consistency(&supercluster, coords.len());
+
+        for zoom in 0..=17 {
+            let clusters = supercluster.get_clusters(-180.0, -90.0, 180.0, 90.0, zoom);
+            for cluster in clusters {
+                assert_eq!(*cluster.properties() as usize, cluster.count());
+            }
+        }
+    }
+}
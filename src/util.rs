@@ -1,5 +1,3 @@
-// TODO: generic over both f64 and f32
-
 use std::f64::consts::PI;
 
 /// longitude/latitude to spherical mercator in [0..1] range
@@ -25,6 +23,54 @@ pub(crate) fn y_to_latitude(y: f64) -> f64 {
     360.0 * f64::atan(f64::exp(y2)) / PI - 90.0
 }
 
+/// Grow the bounding box `bounds` to contain `other`.
+pub(crate) fn extend_bounds(bounds: &mut [f64; 4], other: &[f64; 4]) {
+    bounds[0] = bounds[0].min(other[0]);
+    bounds[1] = bounds[1].min(other[1]);
+    bounds[2] = bounds[2].max(other[2]);
+    bounds[3] = bounds[3].max(other[3]);
+}
+
+/// The convex hull of `points`, as its vertices in counter-clockwise order starting from the
+/// lowest x, without repeating the first vertex.
+///
+/// This uses Andrew's monotone chain algorithm. Collinear points on the hull are left out, so a
+/// single point or a set of collinear points has a hull of one or two vertices.
+pub(crate) fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Whether the turn from a to b to c is counter-clockwise
+    let ccw = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0) > 0.0
+    };
+
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() + 1);
+
+    // Lower hull, from left to right
+    for point in &points {
+        while hull.len() >= 2 && !ccw(hull[hull.len() - 2], hull[hull.len() - 1], *point) {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+
+    // Upper hull, from right to left, ending at the first point
+    let lower_len = hull.len() + 1;
+    for point in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && !ccw(hull[hull.len() - 2], hull[hull.len() - 1], *point) {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+
+    hull.pop();
+    hull
+}
+
 // Note: these tests were copied from supercluster-rs under the MIT license
 // https://github.com/chargetrip/supercluster-rust/blob/d722a680406c494aedc0c36fae05a10aec854d4d/src/lib.rs#L893-L925
 #[cfg(test)]
@@ -64,4 +110,28 @@ mod test {
         assert_eq!(y_to_latitude(0.875), -79.17133464081944);
         assert_eq!(y_to_latitude(0.125), 79.17133464081945);
     }
+
+    #[test]
+    fn test_convex_hull() {
+        let points = vec![
+            (1.0, 1.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.5, 1.5),
+            (0.0, 0.0),
+        ];
+        assert_eq!(
+            convex_hull(points),
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]
+        );
+
+        assert_eq!(convex_hull(vec![(1.0, 1.0), (1.0, 1.0)]), vec![(1.0, 1.0)]);
+        assert_eq!(
+            convex_hull(vec![(2.0, 2.0), (0.0, 0.0), (1.0, 1.0)]),
+            vec![(0.0, 0.0), (2.0, 2.0)]
+        );
+    }
 }